@@ -1,12 +1,18 @@
-use crate::{Cp, Deactivate, Exclude, Focus, Include, Mv, New, Rm};
+use crate::{
+    Adopt, Cp, Deactivate, Exclude, Extract, Focus, GlobStrategy, Include, InheritStrategy, List,
+    ListedPackage, Log, Mv, New, Plan, PlanFile, RenameStrategy, Restore, Rm, Undo, Unfocus,
+    UntrackedStrategy,
+};
 use anyhow::{bail, Context as _};
 use cargo_metadata::Metadata;
 use easy_ext::ext;
 use env_logger::fmt::WriteStyle;
+use itertools::Itertools as _;
 use serde::Deserialize;
 use std::{
     env,
-    io::Write as _,
+    io::{self, Write as _},
+    iter,
     path::{Path, PathBuf},
     process::{self, Stdio},
     str,
@@ -45,6 +51,14 @@ pub enum CargoMember {
     #[structopt(author, visible_alias("f"))]
     Focus(CargoMemberFocus),
 
+    /// Restore `workspace` saved by `focus`
+    #[structopt(author, visible_alias("u"))]
+    Unfocus(CargoMemberUnfocus),
+
+    /// List packages under the workspace root
+    #[structopt(author, visible_alias("l"))]
+    List(CargoMemberList),
+
     /// Create a new workspace member with `cargo new`
     #[structopt(author, visible_alias("n"))]
     New(CargoMemberNew),
@@ -60,6 +74,30 @@ pub enum CargoMember {
     /// Move a workspace member
     #[structopt(author, visible_alias("m"))]
     Mv(CargoMemberMv),
+
+    /// Put back packages moved to the trash by `rm --trash`
+    #[structopt(author)]
+    Restore(CargoMemberRestore),
+
+    /// Revert the latest command recorded in the journal
+    #[structopt(author)]
+    Undo(CargoMemberUndo),
+
+    /// Show the commands recorded in the journal
+    #[structopt(author)]
+    Log(CargoMemberLog),
+
+    /// Run the steps in a plan file with a single lock file update
+    #[structopt(author)]
+    Apply(CargoMemberApply),
+
+    /// Move a workspace member out of the workspace as a standalone package
+    #[structopt(author, visible_alias("x"))]
+    Extract(CargoMemberExtract),
+
+    /// Move a standalone package into the workspace
+    #[structopt(author, visible_alias("a"))]
+    Adopt(CargoMemberAdopt),
 }
 
 impl CargoMember {
@@ -69,10 +107,18 @@ impl CargoMember {
             | Self::Exclude(CargoMemberExclude { color, .. })
             | Self::Deactivate(CargoMemberDeactivate { color, .. })
             | Self::Focus(CargoMemberFocus { color, .. })
+            | Self::Unfocus(CargoMemberUnfocus { color, .. })
+            | Self::List(CargoMemberList { color, .. })
             | Self::New(CargoMemberNew { color, .. })
             | Self::Cp(CargoMemberCp { color, .. })
             | Self::Rm(CargoMemberRm { color, .. })
-            | Self::Mv(CargoMemberMv { color, .. }) => color,
+            | Self::Mv(CargoMemberMv { color, .. })
+            | Self::Restore(CargoMemberRestore { color, .. })
+            | Self::Undo(CargoMemberUndo { color, .. })
+            | Self::Log(CargoMemberLog { color, .. })
+            | Self::Apply(CargoMemberApply { color, .. })
+            | Self::Extract(CargoMemberExtract { color, .. })
+            | Self::Adopt(CargoMemberAdopt { color, .. }) => color,
         }
     }
 }
@@ -100,10 +146,26 @@ pub struct CargoMemberInclude {
     #[structopt(long)]
     pub force: bool,
 
+    /// Merge nested workspaces and drop `package.workspace` instead of failing
+    #[structopt(long)]
+    pub absorb: bool,
+
+    /// Add the packages to `workspace.default-members`
+    #[structopt(long, conflicts_with("no-default"))]
+    pub default: bool,
+
+    /// Remove the packages from `workspace.default-members`
+    #[structopt(long)]
+    pub no_default: bool,
+
     /// Dry run. Also enables `--frozen` and `--locked`
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
     /// Paths to include
     pub paths: Vec<PathBuf>,
 }
@@ -135,6 +197,19 @@ pub struct CargoMemberExclude {
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// How to remove packages matched by glob patterns in `workspace.members`
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(GlobStrategy::VARIANTS),
+        default_value("exclude")
+    )]
+    pub glob_strategy: GlobStrategy,
+
     /// Paths to exclude
     pub paths: Vec<PathBuf>,
 }
@@ -166,6 +241,19 @@ pub struct CargoMemberDeactivate {
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// How to remove packages matched by glob patterns in `workspace.members`
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(GlobStrategy::VARIANTS),
+        default_value("exclude")
+    )]
+    pub glob_strategy: GlobStrategy,
+
     /// Paths to exclude
     pub paths: Vec<PathBuf>,
 }
@@ -173,13 +261,34 @@ pub struct CargoMemberDeactivate {
 #[derive(StructOpt, Debug)]
 pub struct CargoMemberFocus {
     /// Add existing packages to `workspace.exclude`
-    #[structopt(long)]
+    #[structopt(long, conflicts_with("default-members"))]
     pub exclude: bool,
 
+    /// Set `workspace.default-members` to the package instead of modifying `workspace.members`
+    #[structopt(long)]
+    pub default_members: bool,
+
+    /// How to remove packages matched by glob patterns in `workspace.members`
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(GlobStrategy::VARIANTS),
+        default_value("exclude")
+    )]
+    pub glob_strategy: GlobStrategy,
+
     /// Dry run. Also enables `--frozen` and `--locked`
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// Proceed even if the affected files have uncommitted changes
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
     /// [cargo] Path to Cargo.toml
     #[structopt(long, value_name("PATH"))]
     pub manifest_path: Option<PathBuf>,
@@ -201,6 +310,68 @@ pub struct CargoMemberFocus {
     pub path: PathBuf,
 }
 
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberUnfocus {
+    /// Overwrite `workspace` even if the manifest has been modified since `focus`
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Shows the restored `workspace` without writing it or removing `focus.json`. Also enables
+    /// `--frozen` and `--locked`
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberList {
+    /// Output format
+    #[structopt(
+        long,
+        value_name("FORMAT"),
+        possible_values(self::ListFormat::VARIANTS),
+        default_value("human")
+    )]
+    pub format: self::ListFormat,
+
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+}
+
 #[derive(StructOpt, Debug)]
 pub struct CargoMemberNew {
     /// [cargo] Path to Cargo.toml
@@ -244,6 +415,10 @@ pub struct CargoMemberNew {
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
     /// [cargo-new] Path
     pub path: PathBuf,
 }
@@ -271,10 +446,35 @@ pub struct CargoMemberCp {
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
     /// Do not modify the `package.name`
     #[structopt(long)]
     pub no_rename: bool,
 
+    /// What to do with fields inherited from the source workspace
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(InheritStrategy::VARIANTS),
+        default_value("add")
+    )]
+    pub inherit_strategy: InheritStrategy,
+
+    /// Also copy files ignored by `.gitignore`, `.ignore` and `.git/info/exclude`
+    #[structopt(long)]
+    pub no_ignore: bool,
+
+    /// Skip files matching the gitignore-style glob
+    #[structopt(long, value_name("GLOB"), number_of_values(1))]
+    pub exclude: Vec<String>,
+
+    /// Also copy VCS directories such as `.git`
+    #[structopt(long)]
+    pub include_vcs: bool,
+
     /// Package ID specification
     pub src: String,
 
@@ -305,7 +505,7 @@ pub struct CargoMemberRm {
     #[structopt(long)]
     pub offline: bool,
 
-    /// Allow non package paths
+    /// Allow non package paths and packages that other members depend on
     #[structopt(long)]
     pub force: bool,
 
@@ -313,6 +513,18 @@ pub struct CargoMemberRm {
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// Proceed even if the affected files have uncommitted changes
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
+    /// Move the directory to `<target dir>/cargo-member/trash` instead of deleting it. See `restore`
+    #[structopt(long)]
+    pub trash: bool,
+
     /// Paths to exclude
     pub paths: Vec<PathBuf>,
 }
@@ -340,10 +552,230 @@ pub struct CargoMemberMv {
     #[structopt(long)]
     pub dry_run: bool,
 
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
     /// Do not modify the `package.name`
     #[structopt(long)]
     pub no_rename: bool,
 
+    /// How to update dependents when the package is renamed
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(RenameStrategy::VARIANTS),
+        default_value("alias")
+    )]
+    pub rename_strategy: RenameStrategy,
+
+    /// What to do with fields inherited from the source workspace
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(InheritStrategy::VARIANTS),
+        default_value("add")
+    )]
+    pub inherit_strategy: InheritStrategy,
+
+    /// Skip files matching the gitignore-style glob
+    #[structopt(long, value_name("GLOB"), number_of_values(1))]
+    pub exclude: Vec<String>,
+
+    /// Also move VCS directories such as `.git`
+    #[structopt(long)]
+    pub include_vcs: bool,
+
+    /// Proceed even if the affected files have uncommitted changes. In a Git work tree, unstaged
+    /// changes in the moved files get staged
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
+    /// Move the directory to `<target dir>/cargo-member/trash` instead of deleting it. See `restore`
+    #[structopt(long)]
+    pub trash: bool,
+
+    /// What to do with untracked and ignored files in a Git work tree
+    #[structopt(
+        long,
+        value_name("STRATEGY"),
+        possible_values(UntrackedStrategy::VARIANTS),
+        default_value("move")
+    )]
+    pub untracked: UntrackedStrategy,
+
+    /// Package ID specification
+    pub src: String,
+
+    /// Directory
+    pub dst: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberRestore {
+    /// Lists the packages that would be put back and shows the manifest diff without moving
+    /// anything. Also enables `--frozen` and `--locked`
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+
+    /// ID of the trash entry. Defaults to the latest one
+    pub entry: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberUndo {
+    /// Overwrite files even if they have been changed since the command
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Dry run
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberLog {
+    /// Output format
+    #[structopt(
+        long,
+        value_name("FORMAT"),
+        possible_values(self::ListFormat::VARIANTS),
+        default_value("human")
+    )]
+    pub format: self::ListFormat,
+
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberApply {
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+
+    /// Checks the whole plan and shows what every step would change without writing anything.
+    /// Also enables `--frozen` and `--locked`
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// Proceed even if the affected files have uncommitted changes
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
+    /// Move the directories removed by `rm` and `mv` steps to `<target dir>/cargo-member/trash`
+    #[structopt(long)]
+    pub trash: bool,
+
+    /// Plan file. Paths in it are relative to the workspace root
+    pub plan: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberExtract {
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+
+    /// Extract the package even if other workspace members depend on it
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Shows the files that would be moved and the manifest diff without writing anything. Also
+    /// enables `--frozen` and `--locked`
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// Proceed even if the affected files have uncommitted changes
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
     /// Package ID specification
     pub src: String,
 
@@ -351,6 +783,52 @@ pub struct CargoMemberMv {
     pub dst: PathBuf,
 }
 
+#[derive(StructOpt, Debug)]
+pub struct CargoMemberAdopt {
+    /// [cargo] Path to Cargo.toml
+    #[structopt(long, value_name("PATH"))]
+    pub manifest_path: Option<PathBuf>,
+
+    /// [cargo] Coloring
+    #[structopt(
+        long,
+        value_name("WHEN"),
+        possible_values(self::ColorChoice::VARIANTS),
+        default_value("auto")
+    )]
+    pub color: self::ColorChoice,
+
+    /// [cargo] Run without accessing the network
+    #[structopt(long)]
+    pub offline: bool,
+
+    /// Keep the original directory
+    #[structopt(long)]
+    pub copy: bool,
+
+    /// Replace fields and dependencies equal to the workspace ones with `workspace = true`
+    #[structopt(long)]
+    pub inherit: bool,
+
+    /// Dry run
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Keep the original contents of overwritten files as `*.bak`
+    #[structopt(long)]
+    pub backup: bool,
+
+    /// Proceed even if the affected files have uncommitted changes
+    #[structopt(long)]
+    pub allow_dirty: bool,
+
+    /// Package directory
+    pub src: PathBuf,
+
+    /// Directory
+    pub dst: PathBuf,
+}
+
 /// Coloring.
 #[derive(EnumString, EnumVariantNames, IntoStaticStr, Clone, Copy, Debug)]
 #[strum(serialize_all = "kebab-case")]
@@ -360,6 +838,14 @@ pub enum ColorChoice {
     Never,
 }
 
+/// Output format of `cargo member list` and `cargo member log`.
+#[derive(EnumString, EnumVariantNames, IntoStaticStr, Clone, Copy, Debug)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ListFormat {
+    Human,
+    Json,
+}
+
 impl From<self::ColorChoice> for WriteStyle {
     fn from(choice: self::ColorChoice) -> Self {
         match choice {
@@ -371,24 +857,38 @@ impl From<self::ColorChoice> for WriteStyle {
 }
 
 #[derive(Debug)]
-pub struct Context<W> {
+pub struct Context<W, O> {
     cwd: PathBuf,
+    stdout: O,
     stderr: W,
     stderr_redirection: Stdio,
 }
 
-impl<W> Context<W> {
+impl<W> Context<W, io::Stdout> {
     pub fn new(stderr: W) -> anyhow::Result<Self> {
         let cwd = env::current_dir().with_context(|| "failed to get CWD")?;
         let stderr_redirection = Stdio::inherit();
         Ok(Self {
             cwd,
+            stdout: io::stdout(),
             stderr,
             stderr_redirection,
         })
     }
 }
 
+impl<W, O> Context<W, O> {
+    /// Where `list` and `log` write their output.
+    pub fn stdout<O2: io::Write>(self, stdout: O2) -> Context<W, O2> {
+        Context {
+            cwd: self.cwd,
+            stdout,
+            stderr: self.stderr,
+            stderr_redirection: self.stderr_redirection,
+        }
+    }
+}
+
 pub fn init_logger(color: self::ColorChoice) {
     env_logger::Builder::from_default_env()
         .write_style(color.into())
@@ -428,25 +928,40 @@ pub fn exit_with_error(error: anyhow::Error, color: self::ColorChoice) -> ! {
     process::exit(101);
 }
 
-pub fn run(opt: CargoMember, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+pub fn run(opt: CargoMember, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
     match opt {
         CargoMember::Include(opt) => include(opt, ctx),
         CargoMember::Exclude(opt) => exclude(opt, ctx),
         CargoMember::Deactivate(opt) => deactivate(opt, ctx),
         CargoMember::Focus(opt) => focus(opt, ctx),
+        CargoMember::Unfocus(opt) => unfocus(opt, ctx),
+        CargoMember::List(opt) => list(opt, ctx),
         CargoMember::New(opt) => new(opt, ctx),
         CargoMember::Cp(opt) => cp(opt, ctx),
         CargoMember::Rm(opt) => rm(opt, ctx),
         CargoMember::Mv(opt) => mv(opt, ctx),
+        CargoMember::Restore(opt) => restore(opt, ctx),
+        CargoMember::Undo(opt) => undo(opt, ctx),
+        CargoMember::Log(opt) => log(opt, ctx),
+        CargoMember::Apply(opt) => apply(opt, ctx),
+        CargoMember::Extract(opt) => extract(opt, ctx),
+        CargoMember::Adopt(opt) => adopt(opt, ctx),
     }
 }
 
-fn include(opt: CargoMemberInclude, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn include(
+    opt: CargoMemberInclude,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
     let CargoMemberInclude {
         manifest_path,
         offline,
         force,
+        absorb,
+        default,
+        no_default,
         dry_run,
+        backup,
         paths,
         ..
     } = opt;
@@ -456,20 +971,35 @@ fn include(opt: CargoMemberInclude, ctx: Context<impl WriteColor>) -> anyhow::Re
     let possibly_empty_workspace_root = find_root_manifest(manifest_path.as_deref(), &cwd)?;
     let paths = paths.into_iter().map(|p| cwd.join(p.trim_leading_dots()));
 
+    let default_member = match (default, no_default) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    };
+
     Include::new(&possibly_empty_workspace_root, paths)
         .force(force)
+        .absorb(absorb)
+        .default_member(default_member)
         .offline(offline)
         .dry_run(dry_run)
+        .backup(backup)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
-fn exclude(opt: CargoMemberExclude, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn exclude(
+    opt: CargoMemberExclude,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
     let CargoMemberExclude {
         package,
         manifest_path,
         offline,
+        glob_strategy,
         dry_run,
+        backup,
         paths,
         ..
     } = opt;
@@ -481,17 +1011,25 @@ fn exclude(opt: CargoMemberExclude, ctx: Context<impl WriteColor>) -> anyhow::Re
     let paths = paths.into_iter().map(|p| cwd.join(p.trim_leading_dots()));
 
     Exclude::from_metadata(&metadata, paths, package)
+        .glob_strategy(glob_strategy)
         .dry_run(dry_run)
+        .backup(backup)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
-fn deactivate(opt: CargoMemberDeactivate, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn deactivate(
+    opt: CargoMemberDeactivate,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
     let CargoMemberDeactivate {
         package,
         manifest_path,
         offline,
+        glob_strategy,
         dry_run,
+        backup,
         paths,
         ..
     } = opt;
@@ -503,15 +1041,25 @@ fn deactivate(opt: CargoMemberDeactivate, ctx: Context<impl WriteColor>) -> anyh
     let paths = paths.into_iter().map(|p| cwd.join(p.trim_leading_dots()));
 
     Deactivate::from_metadata(&metadata, paths, package)
+        .glob_strategy(glob_strategy)
         .dry_run(dry_run)
+        .backup(backup)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
-fn focus(opt: CargoMemberFocus, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn focus(
+    opt: CargoMemberFocus,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
     let CargoMemberFocus {
         exclude,
+        default_members,
+        glob_strategy,
         dry_run,
+        backup,
+        allow_dirty,
         manifest_path,
         offline,
         path,
@@ -526,13 +1074,110 @@ fn focus(opt: CargoMemberFocus, ctx: Context<impl WriteColor>) -> anyhow::Result
 
     Focus::new(&workspace_root, &path)
         .dry_run(dry_run)
+        .backup(backup)
+        .allow_dirty(allow_dirty)
         .offline(offline)
         .exclude(exclude)
+        .default_members(default_members)
+        .glob_strategy(glob_strategy)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
+}
+
+fn unfocus(
+    opt: CargoMemberUnfocus,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
+    let CargoMemberUnfocus {
+        force,
+        dry_run,
+        backup,
+        manifest_path,
+        offline,
+        ..
+    } = opt;
+
+    let Context { cwd, stderr, .. } = ctx;
+
+    let Metadata { workspace_root, .. } =
+        crate::cargo_metadata(manifest_path.as_deref(), dry_run, dry_run, offline, &cwd)?;
+
+    Unfocus::new(&workspace_root)
+        .force(force)
+        .dry_run(dry_run)
+        .backup(backup)
+        .offline(offline)
+        .stderr(stderr)
+        .exec()
+        .map_err(Into::into)
+}
+
+fn list(opt: CargoMemberList, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
+    let CargoMemberList {
+        format,
+        manifest_path,
+        offline,
+        ..
+    } = opt;
+
+    let Context {
+        cwd,
+        mut stdout,
+        stderr,
+        ..
+    } = ctx;
+
+    let metadata = crate::cargo_metadata(manifest_path.as_deref(), false, false, offline, &cwd)?;
+
+    let packages = List::from_metadata(&metadata).stderr(stderr).exec()?;
+
+    match format {
+        self::ListFormat::Human => {
+            let rows = packages
+                .iter()
+                .map(
+                    |ListedPackage {
+                         path,
+                         name,
+                         status,
+                         default_member,
+                     }| {
+                        [
+                            &**path,
+                            name.as_deref().unwrap_or("-"),
+                            status.into(),
+                            if *default_member { "yes" } else { "no" },
+                        ]
+                    },
+                )
+                .collect::<Vec<_>>();
+            let header = ["PATH", "NAME", "STATUS", "DEFAULT"];
+            let widths = (0..header.len())
+                .map(|i| {
+                    rows.iter()
+                        .map(|r| r[i].len())
+                        .chain(iter::once(header[i].len()))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect::<Vec<_>>();
+            for row in iter::once(&header).chain(&rows) {
+                let line = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(s, &w)| format!("{:<w$}", s, w = w))
+                    .join("  ");
+                writeln!(stdout, "{}", line.trim_end())?;
+            }
+        }
+        self::ListFormat::Json => writeln!(stdout, "{}", serde_json::to_string(&packages)?)?,
+    }
+    stdout.flush()?;
+    Ok(())
 }
 
-fn new(opt: CargoMemberNew, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn new(opt: CargoMemberNew, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
     let CargoMemberNew {
         manifest_path,
         registry,
@@ -541,6 +1186,7 @@ fn new(opt: CargoMemberNew, ctx: Context<impl WriteColor>) -> anyhow::Result<()>
         name,
         offline,
         dry_run,
+        backup,
         path,
         ..
     } = opt;
@@ -549,6 +1195,7 @@ fn new(opt: CargoMemberNew, ctx: Context<impl WriteColor>) -> anyhow::Result<()>
         cwd,
         stderr,
         stderr_redirection,
+        ..
     } = ctx;
 
     let possibly_empty_workspace_root = find_root_manifest(manifest_path.as_deref(), &cwd)?;
@@ -562,18 +1209,25 @@ fn new(opt: CargoMemberNew, ctx: Context<impl WriteColor>) -> anyhow::Result<()>
         .cargo_new_stderr_redirection(stderr_redirection)
         .offline(offline)
         .dry_run(dry_run)
+        .backup(backup)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
-fn cp(opt: CargoMemberCp, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn cp(opt: CargoMemberCp, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
     let CargoMemberCp {
         manifest_path,
         offline,
         dry_run,
+        backup,
         no_rename,
+        inherit_strategy,
         src,
         dst,
+        no_ignore,
+        exclude,
+        include_vcs,
         ..
     } = opt;
 
@@ -585,18 +1239,27 @@ fn cp(opt: CargoMemberCp, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
 
     Cp::from_metadata(&metadata, &src, &dst)
         .dry_run(dry_run)
+        .backup(backup)
         .no_rename(no_rename)
+        .inherit_strategy(inherit_strategy)
+        .no_ignore(no_ignore)
+        .exclude(exclude)
+        .include_vcs(include_vcs)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
-fn rm(opt: CargoMemberRm, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn rm(opt: CargoMemberRm, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
     let CargoMemberRm {
         package,
         manifest_path,
         offline,
         force,
         dry_run,
+        backup,
+        allow_dirty,
+        trash,
         paths,
         ..
     } = opt;
@@ -610,18 +1273,31 @@ fn rm(opt: CargoMemberRm, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
     Rm::from_metadata(&metadata, paths, package)
         .force(force)
         .dry_run(dry_run)
+        .offline(offline)
+        .backup(backup)
+        .allow_dirty(allow_dirty)
+        .trash(trash)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
-fn mv(opt: CargoMemberMv, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
+fn mv(opt: CargoMemberMv, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
     let CargoMemberMv {
         manifest_path,
         offline,
         dry_run,
+        backup,
         no_rename,
+        rename_strategy,
+        inherit_strategy,
         src,
         dst,
+        exclude,
+        include_vcs,
+        allow_dirty,
+        trash,
+        untracked,
         ..
     } = opt;
 
@@ -633,9 +1309,225 @@ fn mv(opt: CargoMemberMv, ctx: Context<impl WriteColor>) -> anyhow::Result<()> {
 
     Mv::from_metadata(&metadata, &src, &dst)
         .dry_run(dry_run)
+        .backup(backup)
         .no_rename(no_rename)
+        .rename_strategy(rename_strategy)
+        .inherit_strategy(inherit_strategy)
+        .exclude(exclude)
+        .include_vcs(include_vcs)
+        .allow_dirty(allow_dirty)
+        .trash(trash)
+        .untracked(untracked)
+        .offline(offline)
+        .stderr(stderr)
+        .exec()
+        .map_err(Into::into)
+}
+
+fn restore(
+    opt: CargoMemberRestore,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
+    let CargoMemberRestore {
+        dry_run,
+        backup,
+        manifest_path,
+        offline,
+        entry,
+        ..
+    } = opt;
+
+    let Context { cwd, stderr, .. } = ctx;
+
+    let Metadata { workspace_root, .. } =
+        crate::cargo_metadata(manifest_path.as_deref(), dry_run, dry_run, offline, &cwd)?;
+
+    Restore::new(&workspace_root)
+        .entry(entry.as_deref())
+        .dry_run(dry_run)
+        .backup(backup)
+        .offline(offline)
+        .stderr(stderr)
+        .exec()
+        .map_err(Into::into)
+}
+
+fn undo(opt: CargoMemberUndo, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
+    let CargoMemberUndo {
+        force,
+        dry_run,
+        manifest_path,
+        offline,
+        ..
+    } = opt;
+
+    let Context { cwd, stderr, .. } = ctx;
+
+    let Metadata { workspace_root, .. } =
+        crate::cargo_metadata(manifest_path.as_deref(), false, false, offline, &cwd)?;
+
+    Undo::new(&workspace_root)
+        .force(force)
+        .dry_run(dry_run)
+        .offline(offline)
+        .stderr(stderr)
+        .exec()
+        .map_err(Into::into)
+}
+
+fn log(opt: CargoMemberLog, ctx: Context<impl WriteColor, impl io::Write>) -> anyhow::Result<()> {
+    let CargoMemberLog {
+        format,
+        manifest_path,
+        offline,
+        ..
+    } = opt;
+
+    let Context {
+        cwd, mut stdout, ..
+    } = ctx;
+
+    let Metadata { workspace_root, .. } =
+        crate::cargo_metadata(manifest_path.as_deref(), false, false, offline, &cwd)?;
+
+    let entries = Log::new(&workspace_root).exec()?;
+
+    match format {
+        self::ListFormat::Human => {
+            let relative = |path: &Path| {
+                path.strip_prefix(&workspace_root)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            };
+            for entry in entries.iter().rev() {
+                writeln!(stdout, "{}  {}", entry.id, entry.operation)?;
+                for file in &entry.modified {
+                    writeln!(stdout, "    modified  {}", relative(&file.path))?;
+                }
+                for path in entry.lockfiles.iter().chain(&entry.unrecorded) {
+                    writeln!(stdout, "    modified  {}", relative(path))?;
+                }
+                for path in &entry.created {
+                    writeln!(stdout, "    created   {}", relative(path))?;
+                }
+                for path in &entry.removed {
+                    writeln!(stdout, "    removed   {}", relative(path))?;
+                }
+                for moved in &entry.moved {
+                    writeln!(
+                        stdout,
+                        "    moved     {} -> {}",
+                        relative(&moved.from),
+                        relative(&moved.to),
+                    )?;
+                }
+            }
+        }
+        self::ListFormat::Json => writeln!(stdout, "{}", serde_json::to_string(&entries)?)?,
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn apply(
+    opt: CargoMemberApply,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
+    let CargoMemberApply {
+        manifest_path,
+        offline,
+        dry_run,
+        backup,
+        allow_dirty,
+        trash,
+        plan,
+        ..
+    } = opt;
+
+    let Context { cwd, stderr, .. } = ctx;
+
+    let metadata =
+        crate::cargo_metadata(manifest_path.as_deref(), dry_run, dry_run, offline, &cwd)?;
+    let PlanFile { steps } = crate::fs::read_toml(cwd.join(plan))?;
+
+    Plan::from_metadata(&metadata, steps)
+        .dry_run(dry_run)
+        .backup(backup)
+        .allow_dirty(allow_dirty)
+        .trash(trash)
+        .offline(offline)
+        .stderr(stderr)
+        .exec()
+        .map_err(Into::into)
+}
+
+fn extract(
+    opt: CargoMemberExtract,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
+    let CargoMemberExtract {
+        manifest_path,
+        offline,
+        force,
+        dry_run,
+        backup,
+        allow_dirty,
+        src,
+        dst,
+        ..
+    } = opt;
+
+    let Context { cwd, stderr, .. } = ctx;
+
+    let metadata =
+        crate::cargo_metadata(manifest_path.as_deref(), dry_run, dry_run, offline, &cwd)?;
+    let dst = cwd.join(dst.trim_leading_dots());
+
+    Extract::from_metadata(&metadata, &src, &dst)
+        .force(force)
+        .dry_run(dry_run)
+        .backup(backup)
+        .allow_dirty(allow_dirty)
+        .offline(offline)
+        .stderr(stderr)
+        .exec()
+        .map_err(Into::into)
+}
+
+fn adopt(
+    opt: CargoMemberAdopt,
+    ctx: Context<impl WriteColor, impl io::Write>,
+) -> anyhow::Result<()> {
+    let CargoMemberAdopt {
+        manifest_path,
+        offline,
+        copy,
+        inherit,
+        dry_run,
+        backup,
+        allow_dirty,
+        src,
+        dst,
+        ..
+    } = opt;
+
+    let Context { cwd, stderr, .. } = ctx;
+
+    let workspace_root = find_root_manifest(manifest_path.as_deref(), &cwd)?;
+    let src = cwd.join(src.trim_leading_dots());
+    let dst = cwd.join(dst.trim_leading_dots());
+
+    Adopt::new(&workspace_root, &src, &dst)
+        .copy(copy)
+        .inherit(inherit)
+        .dry_run(dry_run)
+        .backup(backup)
+        .allow_dirty(allow_dirty)
+        .offline(offline)
         .stderr(stderr)
         .exec()
+        .map_err(Into::into)
 }
 
 fn find_root_manifest(manifest_path: Option<&Path>, cwd: &Path) -> anyhow::Result<PathBuf> {
@@ -0,0 +1,116 @@
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned from `exec`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The workspace root itself was given as a member.
+    WorkspaceRoot {
+        path: PathBuf,
+    },
+    /// The path does not contain `Cargo.toml`. The operation requires `force`.
+    NotAPackage {
+        path: PathBuf,
+        action: &'static str,
+    },
+    /// The package has its own `[workspace]`. The operation requires `absorb`.
+    NestedWorkspace {
+        path: PathBuf,
+    },
+    /// The package belongs to another workspace via `package.workspace`. The operation requires
+    /// `absorb`.
+    ForeignWorkspace {
+        path: PathBuf,
+        workspace: PathBuf,
+    },
+    /// `workspace.{field}` is not an array of strings.
+    NotAnArray {
+        field: String,
+    },
+    /// The package spec does not match any workspace member.
+    NotAMember {
+        spec: String,
+    },
+    /// Tracked files in the affected paths have uncommitted changes. The operation requires
+    /// `allow_dirty`.
+    Dirty {
+        paths: Vec<PathBuf>,
+        action: &'static str,
+    },
+    /// A Cargo subprocess failed.
+    Cargo {
+        args: Vec<String>,
+        message: String,
+    },
+    Other(anyhow::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WorkspaceRoot { path } => write!(f, "`{}` is the workspace root", path.display()),
+            Self::NotAPackage { path, action } => write!(
+                f,
+                "`{}` does not seem to be a package. enable `--force` to {}",
+                path.display(),
+                action,
+            ),
+            Self::NestedWorkspace { path } => write!(
+                f,
+                "`{}` is the root of another workspace. enable `--absorb` to merge it",
+                path.display(),
+            ),
+            Self::ForeignWorkspace { path, workspace } => write!(
+                f,
+                "`{}` belongs to the workspace at `{}`. enable `--absorb` to remove \
+                 `package.workspace`",
+                path.display(),
+                workspace.display(),
+            ),
+            Self::NotAnArray { field } => write!(f, "`workspace.{}` must be an array", field),
+            Self::NotAMember { spec } => {
+                write!(f, "package `{}` is not a member of the workspace", spec)
+            }
+            Self::Dirty { paths, action } => {
+                write!(
+                    f,
+                    "{} file(s) have uncommitted changes. enable `--allow-dirty` to {}",
+                    paths.len(),
+                    action,
+                )?;
+                for path in paths {
+                    write!(f, "\n{}", path.display())?;
+                }
+                Ok(())
+            }
+            Self::Cargo { message, .. } => write!(f, "{}", message),
+            Self::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Other(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    /// Recovers the variant if the error is an `Error` with no context added, so that the
+    /// contexts are kept otherwise.
+    fn from(err: anyhow::Error) -> Self {
+        if err.chain().next().is_some_and(|e| e.is::<Self>()) {
+            err.downcast().unwrap_or_else(Self::Other)
+        } else {
+            Self::Other(err)
+        }
+    }
+}
@@ -1,7 +1,439 @@
+use crate::{
+    journal::{JournalEntry, ModifiedFile, MovedPath},
+    WriteColorExt as _,
+};
 use anyhow::Context as _;
 use log::debug;
 use serde::de::DeserializeOwned;
-use std::path::Path;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    io::Write as _,
+    path::{Path, PathBuf},
+    process, str,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use termcolor::WriteColor;
+
+thread_local! {
+    static TRANSACTION: RefCell<Option<Transaction>> = const { RefCell::new(None) };
+}
+
+/// Modifications made in the current `transaction`.
+#[derive(Default, Debug)]
+struct Transaction {
+    depth: usize,
+    undo: Vec<Undo>,
+    staged_removals: Vec<PathBuf>,
+    /// Files "written" in dry run, so that later reads in the same transaction see them.
+    dry_run_writes: HashMap<PathBuf, String>,
+    /// Files to show as diffs at the end of a dry run, with the path and contents to compare with.
+    dry_run_diffs: Vec<(PathBuf, PathBuf, String)>,
+    /// Validations run on the final state of the files before committing.
+    checks: Vec<(PathBuf, Check)>,
+    /// Whether `write` keeps the original contents as `<file name>.bak`.
+    backup: bool,
+    backed_up: HashSet<PathBuf>,
+    /// The workspace root and the subcommand to record in the journal.
+    journal: Option<(PathBuf, &'static str)>,
+    /// The target directories of the workspaces, resolved once per transaction.
+    target_dirs: HashMap<PathBuf, PathBuf>,
+    /// Workspaces whose lock file update is left to the caller of `defer_lock_updates`.
+    deferred_lock_updates: Option<Vec<PathBuf>>,
+}
+
+type Check = fn(&Path) -> anyhow::Result<()>;
+
+#[derive(Debug)]
+enum Undo {
+    /// Puts back the previous contents of a file.
+    Restore(PathBuf, Vec<u8>),
+    /// Removes a file or a directory that did not exist.
+    Remove(PathBuf),
+    /// Moves a directory staged for removal back to its original location.
+    Unstage { original: PathBuf, staged: PathBuf },
+    /// Moves a renamed file or directory back.
+    Rename { from: PathBuf, to: PathBuf },
+    /// Creates a removed empty directory again.
+    CreateDir(PathBuf),
+}
+
+impl Undo {
+    fn exec(self) -> anyhow::Result<()> {
+        match self {
+            Self::Restore(path, contents) => write_atomically(&path, &contents)
+                .with_context(|| format!("failed to restore `{}`", path.display())),
+            Self::Remove(path) => match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => remove_dir_all::remove_dir_all(&path),
+                Ok(metadata) if metadata.file_type().is_symlink() => remove_symlink(&path),
+                Ok(_) => std::fs::remove_file(&path),
+                Err(_) => Ok(()),
+            }
+            .with_context(|| format!("failed to remove `{}`", path.display())),
+            Self::Unstage { original, staged } => {
+                std::fs::rename(&staged, &original).with_context(|| {
+                    format!(
+                        "failed to move `{}` back to `{}`",
+                        staged.display(),
+                        original.display(),
+                    )
+                })?;
+                remove_staging_dir(&staged);
+                Ok(())
+            }
+            Self::Rename { from, to } => std::fs::rename(&to, &from).with_context(|| {
+                format!(
+                    "failed to move `{}` back to `{}`",
+                    to.display(),
+                    from.display(),
+                )
+            }),
+            Self::CreateDir(path) => std::fs::create_dir(&path)
+                .with_context(|| format!("failed to create `{}`", path.display())),
+        }
+    }
+}
+
+/// Runs `f` so that every modification made through this module is reverted if `f` fails.
+///
+/// Nested calls join the outermost transaction. Directories removed in a transaction are moved
+/// aside and deleted only after the outermost one succeeds. A failure to write the journal is
+/// reported to `stderr` as a warning.
+pub(crate) fn transaction<T, W: WriteColor>(
+    stderr: &mut W,
+    f: impl FnOnce(&mut W) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    TRANSACTION.with(|tx| tx.borrow_mut().get_or_insert_with(Default::default).depth += 1);
+
+    let result = f(stderr);
+
+    let tx = TRANSACTION.with(|tx| {
+        let mut tx = tx.borrow_mut();
+        let inner = tx.as_mut().expect("should be in a transaction");
+        inner.depth -= 1;
+        if inner.depth == 0 {
+            tx.take()
+        } else {
+            None
+        }
+    });
+
+    let tx = match tx {
+        Some(tx) => tx,
+        None => return result,
+    };
+
+    let result = result.and_then(|ret| {
+        run_checks(&tx.checks)?;
+        Ok(ret)
+    });
+
+    match result {
+        Ok(ret) => {
+            for staged in tx.staged_removals {
+                remove_dir_all::remove_dir_all(&staged)
+                    .with_context(|| format!("failed to remove `{}`", staged.display()))?;
+                remove_staging_dir(&staged);
+                debug!("Removed {}", staged.display());
+            }
+            // The changes are in place, so failing to record them does not fail the command.
+            if let Some((workspace_root, operation)) = tx.journal {
+                let target_dir = &tx.target_dirs[&workspace_root];
+                let result = journal_entry(operation, &tx.undo).and_then(|entry| {
+                    if entry.is_empty() {
+                        return Ok(());
+                    }
+                    crate::journal::append(target_dir, &entry)
+                });
+                if let Err(err) = result {
+                    stderr.warn(format!("{:#}", err))?;
+                }
+            }
+            Ok(ret)
+        }
+        Err(err) => {
+            for undo in tx.undo.into_iter().rev() {
+                debug!("Rolling back: {:?}", undo);
+                if let Err(rollback_err) = undo.exec() {
+                    return Err(err.context(format!("{:#}. could not roll back", rollback_err)));
+                }
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Makes `write` in the current transaction keep the original contents of overwritten files as
+/// `<file name>.bak`. Once enabled, it stays enabled until the outermost transaction ends.
+pub(crate) fn backup(enabled: bool) {
+    TRANSACTION.with(|tx| {
+        if let Some(tx) = &mut *tx.borrow_mut() {
+            tx.backup |= enabled;
+        }
+    });
+}
+
+/// Makes the outermost transaction append what it did to the journal of `workspace_root` when it
+/// commits. Only the first call in a transaction counts, so nested commands are recorded as part
+/// of the outer one.
+pub(crate) fn journal(workspace_root: &Path, operation: &'static str) {
+    // Resolved while the workspace is still intact.
+    target_dir(workspace_root);
+    TRANSACTION.with(|tx| {
+        if let Some(tx) = &mut *tx.borrow_mut() {
+            tx.journal
+                .get_or_insert_with(|| (workspace_root.to_owned(), operation));
+        }
+    });
+}
+
+/// Returns the target directory of the workspace at `workspace_root`, which holds the journal, the
+/// trash and the state of `focus`. In a transaction, it is resolved only once.
+pub(crate) fn target_dir(workspace_root: &Path) -> PathBuf {
+    let cached = TRANSACTION.with(|tx| {
+        let tx = tx.borrow();
+        tx.as_ref()?.target_dirs.get(workspace_root).cloned()
+    });
+    if let Some(target_dir) = cached {
+        return target_dir;
+    }
+    let target_dir = crate::target_dir(workspace_root);
+    TRANSACTION.with(|tx| {
+        if let Some(tx) = &mut *tx.borrow_mut() {
+            tx.target_dirs
+                .insert(workspace_root.to_owned(), target_dir.clone());
+        }
+    });
+    target_dir
+}
+
+/// Makes the outermost command show the changes to `path` as a diff against `orig` at the end of
+/// a dry run. Only the first call for each path counts, so that a file written several times is
+/// shown once.
+pub(crate) fn dry_run_diff(path: &Path, orig_path: &Path, orig: &str) {
+    TRANSACTION.with(|tx| {
+        if let Some(tx) = &mut *tx.borrow_mut() {
+            if !tx.dry_run_diffs.iter().any(|(p, _, _)| p == path) {
+                tx.dry_run_diffs
+                    .push((path.to_owned(), orig_path.to_owned(), orig.to_owned()));
+            }
+        }
+    });
+}
+
+/// Takes the diffs recorded by `dry_run_diff` as `(orig_path, orig, path, modified)`. Nested
+/// transactions get none, so that they are printed once by the outermost command.
+pub(crate) fn take_dry_run_diffs() -> Vec<(PathBuf, String, PathBuf, String)> {
+    TRANSACTION.with(|tx| {
+        let mut tx = tx.borrow_mut();
+        let tx = match tx.as_mut().filter(|tx| tx.depth == 1) {
+            Some(tx) => tx,
+            None => return vec![],
+        };
+        let diffs = std::mem::take(&mut tx.dry_run_diffs);
+        diffs
+            .into_iter()
+            .map(|(path, orig_path, orig)| {
+                let modified = tx.dry_run_writes.get(&path).cloned().unwrap_or_default();
+                (orig_path, orig, path, modified)
+            })
+            .collect()
+    })
+}
+
+/// Makes `defer_lock_update` in the current transaction record the workspaces instead of
+/// letting the lock files be updated one command at a time.
+pub(crate) fn defer_lock_updates() {
+    TRANSACTION.with(|tx| {
+        if let Some(tx) = &mut *tx.borrow_mut() {
+            tx.deferred_lock_updates.get_or_insert_with(Vec::new);
+        }
+    });
+}
+
+/// Returns `true` if the lock file update of `workspace_root` is deferred, recording it.
+pub(crate) fn defer_lock_update(workspace_root: &Path) -> bool {
+    TRANSACTION.with(|tx| match &mut *tx.borrow_mut() {
+        Some(Transaction {
+            deferred_lock_updates: Some(roots),
+            ..
+        }) => {
+            if !roots.iter().any(|r| r == workspace_root) {
+                roots.push(workspace_root.to_owned());
+            }
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Stops deferring and returns the workspaces whose lock files need to be updated.
+pub(crate) fn take_deferred_lock_updates() -> Vec<PathBuf> {
+    TRANSACTION.with(|tx| {
+        tx.borrow_mut()
+            .as_mut()
+            .and_then(|tx| tx.deferred_lock_updates.take())
+            .unwrap_or_default()
+    })
+}
+
+/// Summarizes the modifications from the undo log. Lock files are listed without their contents,
+/// and files that are not UTF-8, such as the Git index, are listed in `unrecorded`.
+fn journal_entry(operation: &str, undo: &[Undo]) -> anyhow::Result<JournalEntry> {
+    let mut entry = JournalEntry {
+        id: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as _,
+        operation: operation.to_owned(),
+        modified: vec![],
+        lockfiles: vec![],
+        unrecorded: vec![],
+        created: vec![],
+        removed: vec![],
+        moved: vec![],
+    };
+    let mut seen = HashSet::new();
+    for undo in undo {
+        match undo {
+            Undo::Restore(path, before) => {
+                if !seen.insert(path) {
+                    continue;
+                }
+                if path.ends_with("Cargo.lock") {
+                    entry.lockfiles.push(path.clone());
+                } else if let Ok(before) = str::from_utf8(before) {
+                    entry.modified.push(ModifiedFile {
+                        path: path.clone(),
+                        before: before.to_owned(),
+                        after: std::fs::read_to_string(path).ok(),
+                    });
+                } else {
+                    entry.unrecorded.push(path.clone());
+                }
+            }
+            Undo::Remove(path) => {
+                if seen.insert(path) {
+                    entry.created.push(path.clone());
+                }
+            }
+            Undo::Unstage { original, .. } => entry.removed.push(original.clone()),
+            Undo::Rename { from, to } => entry.moved.push(MovedPath {
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            // `undo` creates the parent directories it needs.
+            Undo::CreateDir(_) => {}
+        }
+    }
+    Ok(entry)
+}
+
+/// Runs `check` on `path` when the outermost transaction is about to commit, rolling back if it
+/// fails. Outside a transaction, runs it immediately.
+pub(crate) fn check_before_commit(path: impl AsRef<Path>, check: Check) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let deferred = TRANSACTION.with(|tx| match &mut *tx.borrow_mut() {
+        Some(tx) => {
+            if !tx.checks.iter().any(|(p, _)| p == path) {
+                tx.checks.push((path.to_owned(), check));
+            }
+            true
+        }
+        None => false,
+    });
+    if !deferred {
+        check(path)?;
+    }
+    Ok(())
+}
+
+/// Runs the checks registered so far, so that Cargo does not see a rejected manifest when it is
+/// about to update the lock file. They are registered again when the files are written again.
+pub(crate) fn run_pending_checks() -> anyhow::Result<()> {
+    let checks = TRANSACTION.with(|tx| {
+        tx.borrow_mut()
+            .as_mut()
+            .map(|tx| std::mem::take(&mut tx.checks))
+            .unwrap_or_default()
+    });
+    run_checks(&checks)
+}
+
+fn run_checks(checks: &[(PathBuf, Check)]) -> anyhow::Result<()> {
+    for (path, check) in checks {
+        if path.exists() {
+            check(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Records the current state of `path` so that it is restored on rollback. Use this before
+/// letting other processes such as Cargo modify files.
+pub(crate) fn track(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !in_transaction() {
+        return Ok(());
+    }
+    let undo = if path.is_file() {
+        let contents =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Undo::Restore(path.to_owned(), contents)
+    } else if !path.exists() {
+        Undo::Remove(path.to_owned())
+    } else {
+        return Ok(());
+    };
+    record(undo);
+    Ok(())
+}
+
+fn in_transaction() -> bool {
+    TRANSACTION.with(|tx| tx.borrow().is_some())
+}
+
+fn record(undo: Undo) {
+    TRANSACTION.with(|tx| {
+        if let Some(tx) = &mut *tx.borrow_mut() {
+            tx.undo.push(undo);
+        }
+    });
+}
+
+/// `<target dir>/cargo-member/staging-<pid>/<n>-<name>`, so that the staged directory is not picked
+/// up by the globs in `workspace.members` when the final state is validated.
+///
+/// Followed by `<parent>/.cargo-member-<pid>/<name>`, which is the only one outside a journaled
+/// transaction and is used when the target directory is on another file system.
+fn staging_paths(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let (parent, name) = path
+        .parent()
+        .and_then(|parent| Some((parent, path.file_name()?)))
+        .with_context(|| format!("`{}` has no parent", path.display()))?;
+    let staging = TRANSACTION.with(|tx| {
+        let tx = tx.borrow();
+        let tx = tx.as_ref()?;
+        let (workspace_root, _) = tx.journal.as_ref()?;
+        let dir = tx
+            .target_dirs
+            .get(workspace_root)?
+            .join("cargo-member")
+            .join(format!("staging-{}", process::id()));
+        let mut file_name = OsString::from(format!("{}-", tx.staged_removals.len()));
+        file_name.push(name);
+        Some(dir.join(file_name))
+    });
+    let fallback = parent
+        .join(format!(".cargo-member-{}", process::id()))
+        .join(name);
+    Ok(staging.into_iter().chain(Some(fallback)).collect())
+}
+
+fn remove_staging_dir(staged: &Path) {
+    if let Some(dir) = staged.parent() {
+        // Fails if another removal is still staged there.
+        let _ = std::fs::remove_dir(dir);
+    }
+}
 
 pub(crate) fn read_toml<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> anyhow::Result<T> {
     let path = path.as_ref();
@@ -11,7 +443,7 @@ pub(crate) fn read_toml<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> anyhow:
     Ok(toml)
 }
 
-pub(crate) fn read_toml_edit(path: impl AsRef<Path>) -> anyhow::Result<toml_edit::Document> {
+pub(crate) fn read_toml_edit(path: impl AsRef<Path>) -> anyhow::Result<toml_edit::DocumentMut> {
     let path = path.as_ref();
     let edit = read_to_string(path)?
         .parse()
@@ -20,7 +452,24 @@ pub(crate) fn read_toml_edit(path: impl AsRef<Path>) -> anyhow::Result<toml_edit
     Ok(edit)
 }
 
-fn read_to_string(path: &Path) -> anyhow::Result<String> {
+pub(crate) fn read_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> anyhow::Result<T> {
+    let path = path.as_ref();
+    let json = serde_json::from_str(&read_to_string(path)?)
+        .with_context(|| format!("failed to parse the JSON file at {}", path.display()))?;
+    debug!("Read the JSON file at {}", path.display());
+    Ok(json)
+}
+
+pub(crate) fn read_to_string(path: impl AsRef<Path>) -> anyhow::Result<String> {
+    let path = path.as_ref();
+    let dry_run_written = TRANSACTION.with(|tx| {
+        tx.borrow()
+            .as_ref()
+            .and_then(|tx| tx.dry_run_writes.get(path).cloned())
+    });
+    if let Some(contents) = dry_run_written {
+        return Ok(contents);
+    }
     std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
 }
 
@@ -30,8 +479,24 @@ pub(crate) fn write(
     dry_run: bool,
 ) -> anyhow::Result<()> {
     let path = path.as_ref();
-    if !dry_run {
-        std::fs::write(path, contents)
+    if dry_run {
+        if let Ok(contents) = str::from_utf8(contents.as_ref()) {
+            TRANSACTION.with(|tx| {
+                if let Some(tx) = &mut *tx.borrow_mut() {
+                    tx.dry_run_writes
+                        .insert(path.to_owned(), contents.to_owned());
+                }
+            });
+        }
+    } else {
+        track(path)?;
+        if let Some(bak) = backup_path(path) {
+            track(&bak)?;
+            std::fs::copy(path, &bak).with_context(|| {
+                format!("failed to copy {} to {}", path.display(), bak.display())
+            })?;
+        }
+        write_atomically(path, contents.as_ref())
             .with_context(|| format!("failed to write {}", path.display()))?;
     }
     debug!(
@@ -42,6 +507,51 @@ pub(crate) fn write(
     Ok(())
 }
 
+/// Returns `<file name>.bak` if `path` is overwritten for the first time in a transaction with
+/// backups enabled.
+fn backup_path(path: &Path) -> Option<PathBuf> {
+    if !path.is_file() {
+        return None;
+    }
+    TRANSACTION.with(|tx| {
+        let mut tx = tx.borrow_mut();
+        let tx = tx.as_mut().filter(|tx| tx.backup)?;
+        if !tx.backed_up.insert(path.to_owned()) {
+            return None;
+        }
+        let mut bak = path.as_os_str().to_owned();
+        bak.push(".bak");
+        Some(bak.into())
+    })
+}
+
+/// Writes to a temporary file in the same directory and renames it to `path`, so that an
+/// interrupted write never leaves a truncated file. The permissions of the original file are kept
+/// and symbolic links are written through.
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let path = match path.symlink_metadata() {
+        Ok(metadata) if metadata.file_type().is_symlink() => path.canonicalize()?,
+        _ => path.to_owned(),
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!(".{}.cargo-member-{}.tmp", file_name, process::id()));
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            std::fs::set_permissions(&tmp, metadata.permissions())?;
+        }
+        std::fs::rename(&tmp, &path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
 pub(crate) fn copy(
     src: impl AsRef<Path>,
     dst: impl AsRef<Path>,
@@ -49,9 +559,19 @@ pub(crate) fn copy(
 ) -> anyhow::Result<()> {
     let (src, dst) = (src.as_ref(), dst.as_ref());
     if !dry_run {
-        std::fs::copy(src, dst).with_context(|| {
-            format!("failed to copy `{}` to `{}`", src.display(), dst.display())
-        })?;
+        track(dst)?;
+        // `std::fs::copy` keeps the permissions. The modification time has to be copied by hand.
+        std::fs::copy(src, dst)
+            .and_then(|_| {
+                let modified = std::fs::metadata(src)?.modified()?;
+                std::fs::File::options()
+                    .write(true)
+                    .open(dst)?
+                    .set_modified(modified)
+            })
+            .with_context(|| {
+                format!("failed to copy `{}` to `{}`", src.display(), dst.display())
+            })?;
     }
     debug!(
         "{}Copied {} to {}",
@@ -62,9 +582,65 @@ pub(crate) fn copy(
     Ok(())
 }
 
+/// Creates a symbolic link at `dst` pointing to `target`.
+pub(crate) fn symlink(
+    target: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let (target, dst) = (target.as_ref(), dst.as_ref());
+    if !dry_run {
+        track(dst)?;
+        create_symlink(target, dst).with_context(|| {
+            format!(
+                "failed to create a symbolic link `{}` to `{}`",
+                dst.display(),
+                target.display(),
+            )
+        })?;
+    }
+    debug!(
+        "{}Linked {} to {}",
+        if dry_run { "[dry-run] " } else { "" },
+        dst.display(),
+        target.display(),
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, dst: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, dst)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, dst: &Path) -> std::io::Result<()> {
+    let resolved = dst
+        .parent()
+        .map_or_else(|| target.to_owned(), |p| p.join(target));
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, dst)
+    } else {
+        std::os::windows::fs::symlink_file(target, dst)
+    }
+}
+
+#[cfg(unix)]
+fn remove_symlink(path: &Path) -> std::io::Result<()> {
+    std::fs::remove_file(path)
+}
+
+#[cfg(windows)]
+fn remove_symlink(path: &Path) -> std::io::Result<()> {
+    std::fs::remove_file(path).or_else(|_| std::fs::remove_dir(path))
+}
+
 pub(crate) fn create_dir_all(path: impl AsRef<Path>, dry_run: bool) -> anyhow::Result<()> {
     let path = path.as_ref();
     if !dry_run {
+        if let Some(topmost) = path.ancestors().take_while(|p| !p.exists()).last() {
+            track(topmost)?;
+        }
         std::fs::create_dir_all(path)
             .with_context(|| format!("failed to create `{}`", path.display()))?;
     }
@@ -76,10 +652,112 @@ pub(crate) fn create_dir_all(path: impl AsRef<Path>, dry_run: bool) -> anyhow::R
     Ok(())
 }
 
+/// Sets the permissions of `path`, which has been created in the current transaction.
+pub(crate) fn set_permissions(
+    path: impl AsRef<Path>,
+    permissions: std::fs::Permissions,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !dry_run {
+        std::fs::set_permissions(path, permissions.clone())
+            .with_context(|| format!("failed to set the permissions of `{}`", path.display()))?;
+    }
+    debug!(
+        "{}Set the permissions of {} to {:?}",
+        if dry_run { "[dry-run] " } else { "" },
+        path.display(),
+        permissions,
+    );
+    Ok(())
+}
+
 pub(crate) fn remove_dir_all(path: impl AsRef<Path>, dry_run: bool) -> anyhow::Result<()> {
     let path = path.as_ref();
     if !dry_run {
-        remove_dir_all::remove_dir_all(path)
+        if in_transaction() {
+            let mut staging_paths = staging_paths(path)?.into_iter().peekable();
+            let staged = loop {
+                let staged = staging_paths.next().expect("should not be empty");
+                let result =
+                    std::fs::create_dir_all(staged.parent().expect("should have a parent"))
+                        .and_then(|()| std::fs::rename(path, &staged));
+                match result {
+                    Ok(()) => break staged,
+                    Err(_) if staging_paths.peek().is_some() => remove_staging_dir(&staged),
+                    Err(err) => {
+                        return Err(err)
+                            .with_context(|| format!("failed to remove `{}`", path.display()));
+                    }
+                }
+            };
+            record(Undo::Unstage {
+                original: path.to_owned(),
+                staged: staged.clone(),
+            });
+            TRANSACTION.with(|tx| {
+                if let Some(tx) = &mut *tx.borrow_mut() {
+                    tx.staged_removals.push(staged);
+                }
+            });
+        } else {
+            remove_dir_all::remove_dir_all(path)
+                .with_context(|| format!("failed to remove `{}`", path.display()))?;
+        }
+    }
+    debug!(
+        "{}Removed {}",
+        if dry_run { "[dry-run] " } else { "" },
+        path.display(),
+    );
+    Ok(())
+}
+
+pub(crate) fn rename(
+    from: impl AsRef<Path>,
+    to: impl AsRef<Path>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if !dry_run {
+        std::fs::rename(from, to).with_context(|| {
+            format!("failed to move `{}` to `{}`", from.display(), to.display())
+        })?;
+        record(Undo::Rename {
+            from: from.to_owned(),
+            to: to.to_owned(),
+        });
+    }
+    debug!(
+        "{}Moved {} to {}",
+        if dry_run { "[dry-run] " } else { "" },
+        from.display(),
+        to.display(),
+    );
+    Ok(())
+}
+
+/// Removes an empty directory.
+pub(crate) fn remove_dir(path: impl AsRef<Path>, dry_run: bool) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !dry_run {
+        std::fs::remove_dir(path)
+            .with_context(|| format!("failed to remove `{}`", path.display()))?;
+        record(Undo::CreateDir(path.to_owned()));
+    }
+    debug!(
+        "{}Removed {}",
+        if dry_run { "[dry-run] " } else { "" },
+        path.display(),
+    );
+    Ok(())
+}
+
+pub(crate) fn remove_file(path: impl AsRef<Path>, dry_run: bool) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !dry_run {
+        track(path)?;
+        std::fs::remove_file(path)
             .with_context(|| format!("failed to remove `{}`", path.display()))?;
     }
     debug!(
@@ -0,0 +1,195 @@
+use crate::Error;
+use anyhow::{bail, Context as _};
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    io,
+    path::{Component, Path, PathBuf},
+    str,
+};
+
+/// Fails with `Error::Dirty` if tracked files under `paths` have staged or unstaged changes.
+///
+/// Paths outside any Git work tree are skipped.
+pub(crate) fn ensure_clean(paths: &[PathBuf], action: &'static str) -> anyhow::Result<()> {
+    let mut dirty = vec![];
+    for path in paths {
+        if let Some(repo) = Repository::discover(path)? {
+            dirty.extend(repo.dirty_files(path)?);
+        }
+    }
+    dirty.sort();
+    dirty.dedup();
+    if !dirty.is_empty() {
+        return Err(Error::Dirty {
+            paths: dirty,
+            action,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// A Git work tree, operated through the `git` executable.
+#[derive(Debug)]
+pub(crate) struct Repository {
+    root: PathBuf,
+    git_dir: PathBuf,
+}
+
+impl Repository {
+    /// Finds the work tree containing `path`, which does not have to exist.
+    ///
+    /// Returns `None` if `path` is not in a work tree or `git` is not installed.
+    pub(crate) fn discover(path: &Path) -> anyhow::Result<Option<Self>> {
+        let dir = path
+            .ancestors()
+            .find(|p| p.is_dir())
+            .with_context(|| format!("no ancestor of `{}` exists", path.display()))?;
+
+        let output = match duct::cmd!("git", "rev-parse", "--show-toplevel", "--absolute-git-dir")
+            .dir(dir)
+            .stdout_capture()
+            .stderr_capture()
+            .unchecked()
+            .run()
+        {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        match *str::from_utf8(&output.stdout)?.lines().collect::<Vec<_>>() {
+            [root, git_dir] => Ok(Some(Self {
+                root: root.into(),
+                git_dir: git_dir.into(),
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        self.relativize(path).is_ok()
+    }
+
+    /// Files under `dir` in the index, relative to `dir`.
+    pub(crate) fn tracked_files(&self, dir: &Path) -> anyhow::Result<BTreeSet<PathBuf>> {
+        let dir = self.relativize(dir)?;
+        let stdout = self.run(&["ls-files".into(), "-z".into(), "--".into(), pathspec(&dir)])?;
+        stdout
+            .split_terminator('\0')
+            .map(|file| Ok(Path::new(file).strip_prefix(&dir)?.to_owned()))
+            .collect()
+    }
+
+    /// Tracked files under `dir` that have staged or unstaged changes.
+    pub(crate) fn dirty_files(&self, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let stdout = self.run(&[
+            "status".into(),
+            "--porcelain".into(),
+            "-z".into(),
+            "--untracked-files=no".into(),
+            "--".into(),
+            pathspec(&self.relativize(dir)?),
+        ])?;
+
+        // `XY <path>\0`, followed by `<original path>\0` for renames and copies.
+        let mut dirty = vec![];
+        let mut entries = stdout.split_terminator('\0');
+        while let Some(entry) = entries.next() {
+            if entry.len() < 4 {
+                bail!("unexpected output from `git status`: {:?}", entry);
+            }
+            dirty.push(self.root.join(&entry[3..]));
+            if entry.starts_with(&['R', 'C'][..]) {
+                entries.next();
+            }
+        }
+        Ok(dirty)
+    }
+
+    /// Stages the move of `src` to `dst` in the index, as `git mv` does.
+    ///
+    /// `files` are the paths under `dst` to add, relative to it. The index is restored if the
+    /// current transaction is rolled back.
+    pub(crate) fn stage_move(
+        &self,
+        src: &Path,
+        dst: &Path,
+        files: &BTreeSet<PathBuf>,
+    ) -> anyhow::Result<()> {
+        crate::fs::track(self.git_dir.join("index"))?;
+
+        self.run(&[
+            "rm".into(),
+            "-r".into(),
+            "-q".into(),
+            "--cached".into(),
+            "--ignore-unmatch".into(),
+            "--".into(),
+            pathspec(&self.relativize(src)?),
+        ])?;
+
+        let dst = self.relativize(dst)?;
+        let mut args = vec!["add".into(), "-f".into(), "--".into()];
+        args.extend(files.iter().map(|file| pathspec(&dst.join(file))));
+        self.run(&args)?;
+        Ok(())
+    }
+
+    /// Makes `path` relative to the root, resolving symbolic links in the part that exists.
+    fn relativize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let (existing, rest) = path
+            .ancestors()
+            .find_map(|p| Some((p.canonicalize().ok()?, path.strip_prefix(p).ok()?)))
+            .with_context(|| format!("no ancestor of `{}` exists", path.display()))?;
+        let root = self.root.canonicalize()?;
+        let relative = existing
+            .strip_prefix(&root)
+            .with_context(|| {
+                format!(
+                    "`{}` is outside the work tree at `{}`",
+                    path.display(),
+                    root.display(),
+                )
+            })?
+            .join(rest);
+        Ok(relative
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect())
+    }
+
+    fn run(&self, args: &[OsString]) -> anyhow::Result<String> {
+        // Paths such as `crates/[a]` are not patterns.
+        let output = duct::cmd("git", args)
+            .dir(&self.root)
+            .env("GIT_LITERAL_PATHSPECS", "1")
+            .stdout_capture()
+            .stderr_capture()
+            .unchecked()
+            .run()?;
+        if !output.status.success() {
+            bail!(
+                "`git {}` failed: {}",
+                args.iter()
+                    .map(|a| a.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                String::from_utf8_lossy(&output.stderr).trim_end(),
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+fn pathspec(relative: &Path) -> OsString {
+    if relative.as_os_str().is_empty() {
+        ".".into()
+    } else {
+        relative.into()
+    }
+}
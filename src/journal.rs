@@ -0,0 +1,139 @@
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+/// A command recorded in `<target dir>/cargo-member/journal.jsonl`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct JournalEntry {
+    /// Milliseconds since the Unix epoch.
+    pub id: u64,
+    /// The subcommand, such as `"rm"`.
+    pub operation: String,
+    /// Text files that existed before the command, except lock files.
+    pub modified: Vec<ModifiedFile>,
+    /// Lock files the command updated. `Undo` lets Cargo update them again instead of restoring
+    /// them.
+    #[serde(default)]
+    pub lockfiles: Vec<PathBuf>,
+    /// Files whose contents are not recorded, such as the Git index. `Undo` refuses to revert the
+    /// command.
+    #[serde(default)]
+    pub unrecorded: Vec<PathBuf>,
+    /// Files and directories that did not exist before the command.
+    pub created: Vec<PathBuf>,
+    /// Directories deleted permanently. They cannot be brought back by `Undo`.
+    pub removed: Vec<PathBuf>,
+    /// Files and directories moved by the command, including the ones moved to the trash.
+    pub moved: Vec<MovedPath>,
+}
+
+impl JournalEntry {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.modified.is_empty()
+            && self.lockfiles.is_empty()
+            && self.unrecorded.is_empty()
+            && self.created.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ModifiedFile {
+    pub path: PathBuf,
+    pub before: String,
+    /// `None` if the command removed the file.
+    pub after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct MovedPath {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// The number of entries kept. Older ones are dropped when a new one is appended.
+const MAX_ENTRIES: usize = 100;
+
+pub(crate) fn path(workspace_root: &Path) -> PathBuf {
+    path_in(&crate::fs::target_dir(workspace_root))
+}
+
+fn path_in(target_dir: &Path) -> PathBuf {
+    target_dir.join("cargo-member").join("journal.jsonl")
+}
+
+/// Reads the entries, oldest first.
+pub(crate) fn read(workspace_root: &Path) -> anyhow::Result<Vec<JournalEntry>> {
+    let path = path(workspace_root);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    crate::fs::read_to_string(&path)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .with_context(|| format!("failed to parse the journal at {}", path.display()))
+        })
+        .collect()
+}
+
+/// Rewrites the journal with `entries`.
+pub(crate) fn write(
+    workspace_root: &Path,
+    entries: &[JournalEntry],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let mut jsonl = String::new();
+    for entry in entries {
+        jsonl += &serde_json::to_string(entry)?;
+        jsonl += "\n";
+    }
+    crate::fs::write(path(workspace_root), jsonl, dry_run)
+}
+
+/// Appends `entry` without going through a transaction. Called after the outermost one commits.
+///
+/// Nothing is recorded if `target_dir` does not exist, so that commands do not create it.
+pub(crate) fn append(target_dir: &Path, entry: &JournalEntry) -> anyhow::Result<()> {
+    if !target_dir.is_dir() {
+        return Ok(());
+    }
+    let path = path_in(target_dir);
+    (|| -> anyhow::Result<_> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let lines = match std::fs::read_to_string(&path) {
+            Ok(jsonl) => jsonl
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err.into()),
+        };
+        if lines.len() < MAX_ENTRIES {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        } else {
+            let mut jsonl = String::new();
+            for line in &lines[lines.len() + 1 - MAX_ENTRIES..] {
+                jsonl += line;
+                jsonl += "\n";
+            }
+            jsonl += &serde_json::to_string(entry)?;
+            jsonl += "\n";
+            crate::fs::write(&path, jsonl, false)?;
+        }
+        Ok(())
+    })()
+    .with_context(|| format!("failed to write the journal at {}", path.display()))
+}
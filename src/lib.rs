@@ -2,25 +2,45 @@
 
 #[doc(hidden)]
 pub mod cli;
+mod error;
 mod fs;
+mod git;
+mod journal;
+mod lockfile;
+mod manifest;
+
+pub use crate::{
+    error::{Error, Result},
+    journal::{JournalEntry, ModifiedFile, MovedPath},
+};
+
+use crate::manifest::{ItemExt as _, TableExt as _};
 
 use anyhow::{anyhow, bail, ensure, Context as _};
-use cargo_metadata::{Metadata, MetadataCommand, Package, Resolve};
+use cargo_metadata::{DepKindInfo, DependencyKind, Metadata, MetadataCommand, Package, Resolve};
 use easy_ext::ext;
-use ignore::{Walk, WalkBuilder};
+use ignore::{
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder,
+};
 use itertools::Itertools as _;
 use log::debug;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeSet, HashMap, HashSet},
     env,
     ffi::{OsStr, OsString},
     fmt::{self, Debug, Display},
     io::{self, Sink},
+    iter,
     ops::Deref,
     path::{Path, PathBuf},
     process::{Command, Stdio},
-    slice, str, vec,
+    slice, str,
+    time::{SystemTime, UNIX_EPOCH},
+    vec,
 };
+use strum::{EnumString, EnumVariantNames, IntoStaticStr};
 use termcolor::{ColorSpec, NoColor, WriteColor};
 use url::Url;
 
@@ -29,7 +49,11 @@ pub struct Include<W> {
     possibly_empty_workspace_root: anyhow::Result<PathBuf>,
     paths: anyhow::Result<Vec<PathBuf>>,
     force: bool,
+    absorb: bool,
+    default_member: Option<bool>,
+    seed_default_members: bool,
     dry_run: bool,
+    backup: bool,
     offline: bool,
     stderr: W,
 }
@@ -43,7 +67,11 @@ impl Include<NoColor<Sink>> {
             possibly_empty_workspace_root: ensure_absolute(possibly_empty_workspace_root),
             paths: paths.into_iter().map(ensure_absolute).collect(),
             force: false,
+            absorb: false,
+            default_member: None,
+            seed_default_members: true,
             dry_run: false,
+            backup: false,
             offline: false,
             stderr: NoColor::new(io::sink()),
         }
@@ -55,10 +83,41 @@ impl<W: WriteColor> Include<W> {
         Self { force, ..self }
     }
 
+    /// Removes `[workspace]` and `package.workspace` from the packages instead of failing, adding
+    /// the members of nested workspaces as well.
+    pub fn absorb(self, absorb: bool) -> Self {
+        Self { absorb, ..self }
+    }
+
+    /// Adds the packages to (`Some(true)`) or removes them from (`Some(false)`)
+    /// `workspace.default-members`.
+    ///
+    /// If the key is missing, it is first created with the current default members.
+    pub fn default_member(self, default_member: Option<bool>) -> Self {
+        Self {
+            default_member,
+            ..self
+        }
+    }
+
+    /// Whether a missing `workspace.default-members` is created with the current default members.
+    /// `Restore` puts the recorded entries back as they were instead.
+    pub(crate) fn seed_default_members(self, seed_default_members: bool) -> Self {
+        Self {
+            seed_default_members,
+            ..self
+        }
+    }
+
     pub fn dry_run(self, dry_run: bool) -> Self {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
     pub fn offline(self, offline: bool) -> Self {
         Self { offline, ..self }
     }
@@ -68,70 +127,105 @@ impl<W: WriteColor> Include<W> {
             possibly_empty_workspace_root: self.possibly_empty_workspace_root,
             paths: self.paths,
             force: self.force,
+            absorb: self.absorb,
+            default_member: self.default_member,
+            seed_default_members: self.seed_default_members,
             dry_run: self.dry_run,
+            backup: self.backup,
             offline: self.offline,
             stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             possibly_empty_workspace_root,
             paths,
             force,
+            absorb,
+            default_member,
+            seed_default_members,
             dry_run,
+            backup,
             offline,
             mut stderr,
         } = self;
-
-        let (possibly_empty_workspace_root, paths) = (possibly_empty_workspace_root?, paths?);
-
-        let modified = paths.iter().try_fold(false, |acc, path| {
-            if !(force || path.join("Cargo.toml").exists()) {
-                return Err(
-                    anyhow!("`{}` does not exist", path.join("Cargo.toml").display()).context(
-                        format!(
-                            "`{}` does not seem to be a package. enable `--force` to add",
-                            path.display(),
-                        ),
-                    ),
-                );
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (possibly_empty_workspace_root, mut paths) =
+                (possibly_empty_workspace_root?, paths?);
+            crate::fs::journal(&possibly_empty_workspace_root, "include");
+
+            let mut i = 0;
+            while i < paths.len() {
+                let path = paths[i].clone();
+                i += 1;
+                if !(force || path.join("Cargo.toml").exists()) {
+                    return Err(Error::NotAPackage {
+                        path,
+                        action: "add",
+                    }
+                    .into());
+                }
+                if path.join("Cargo.toml").exists() {
+                    paths.extend(absorb_workspace(
+                        &possibly_empty_workspace_root,
+                        &path,
+                        absorb,
+                        dry_run,
+                        &mut stderr,
+                    )?);
+                }
             }
-            modify_members(
-                &possibly_empty_workspace_root,
-                &[path],
-                &[],
-                &[],
-                &[path],
-                dry_run,
-                &mut stderr,
-            )
-            .map(|p| acc | p)
-        })?;
 
-        if !modified {
-            stderr.warn("`workspace` unchanged")?;
-        }
+            let seeded = match default_member {
+                Some(default_member) if seed_default_members => crate::seed_default_members(
+                    &possibly_empty_workspace_root,
+                    default_member,
+                    dry_run,
+                    &mut stderr,
+                )?,
+                _ => false,
+            };
+
+            let modified = paths.iter().try_fold(seeded, |acc, path| {
+                let (add_to_default_members, rm_from_default_members) = match default_member {
+                    Some(true) => (&[&**path][..], &[][..]),
+                    Some(false) => (&[][..], &[&**path][..]),
+                    None => (&[][..], &[][..]),
+                };
+                modify_members(
+                    &possibly_empty_workspace_root,
+                    &[path],
+                    &[],
+                    &[],
+                    &[path],
+                    add_to_default_members,
+                    rm_from_default_members,
+                    None,
+                    dry_run,
+                    &mut stderr,
+                )
+                .map(|p| acc | p)
+            })?;
+
+            if !modified {
+                stderr.warn("`workspace` unchanged")?;
+            }
 
-        if dry_run {
-            stderr.warn("not modifying the manifest due to dry run")?;
-        } else if paths.iter().all(|p| p.exists()) {
-            stderr.status(
-                "Updating",
-                possibly_empty_workspace_root.join("Cargo.lock").display(),
-            )?;
+            stderr.dry_run_diffs()?;
 
-            cargo_metadata(
-                Some(&possibly_empty_workspace_root.join("Cargo.toml")),
-                false,
-                false,
-                offline,
-                &possibly_empty_workspace_root,
-            )?;
-        } else if !force {
-            bail!("some packages do not exist");
-        }
-        Ok(())
+            if dry_run {
+                stderr.warn("not modifying the manifest due to dry run")?;
+            } else if paths.iter().all(|p| p.exists()) {
+                update_lockfile(&possibly_empty_workspace_root, offline, &mut stderr)?;
+            } else if !force {
+                bail!("some packages do not exist");
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
@@ -139,7 +233,9 @@ impl<W: WriteColor> Include<W> {
 pub struct Exclude<W> {
     workspace_root: anyhow::Result<PathBuf>,
     paths: anyhow::Result<Vec<PathBuf>>,
+    glob_strategy: GlobStrategy,
     dry_run: bool,
+    backup: bool,
     stderr: W,
 }
 
@@ -151,7 +247,9 @@ impl Exclude<NoColor<Sink>> {
         Self {
             workspace_root: ensure_absolute(workspace_root),
             paths: paths.into_iter().map(ensure_absolute).collect(),
+            glob_strategy: GlobStrategy::default(),
             dry_run: false,
+            backup: false,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -180,67 +278,87 @@ impl Exclude<NoColor<Sink>> {
                         .to_owned())
                 }))
                 .collect(),
+            glob_strategy: GlobStrategy::default(),
             dry_run: false,
+            backup: false,
             stderr: NoColor::new(io::sink()),
         }
     }
 }
 
 impl<W: WriteColor> Exclude<W> {
+    pub fn glob_strategy(self, glob_strategy: GlobStrategy) -> Self {
+        Self {
+            glob_strategy,
+            ..self
+        }
+    }
+
     pub fn dry_run(self, dry_run: bool) -> Self {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
     pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Exclude<W2> {
         Exclude {
             workspace_root: self.workspace_root,
             paths: self.paths,
+            glob_strategy: self.glob_strategy,
             dry_run: self.dry_run,
+            backup: self.backup,
             stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             mut stderr,
             workspace_root,
             paths,
+            glob_strategy,
             dry_run,
+            backup,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (workspace_root, paths) = (workspace_root?, paths?);
+            crate::fs::journal(&workspace_root, "exclude");
+
+            let modified = paths.iter().try_fold(false, |acc, path| {
+                modify_members(
+                    &workspace_root,
+                    &[],
+                    &[path],
+                    &[path],
+                    &[],
+                    &[],
+                    &[],
+                    Some(glob_strategy),
+                    dry_run,
+                    &mut stderr,
+                )
+                .map(|p| acc | p)
+            })?;
+
+            if !modified {
+                stderr.warn("`workspace` unchanged")?;
+            }
 
-        let (workspace_root, paths) = (workspace_root?, paths?);
-
-        let modified = paths.iter().try_fold(false, |acc, path| {
-            modify_members(
-                &workspace_root,
-                &[],
-                &[path],
-                &[path],
-                &[],
-                dry_run,
-                &mut stderr,
-            )
-            .map(|p| acc | p)
-        })?;
-
-        if !modified {
-            stderr.warn("`workspace` unchanged")?;
-        }
+            stderr.dry_run_diffs()?;
 
-        if dry_run {
-            stderr.warn("not modifying the manifest due to dry run")?;
-        } else if !is_empty_workspace(&workspace_root.join("Cargo.toml"))? {
-            stderr.status("Updating", workspace_root.join("Cargo.lock").display())?;
-
-            cargo_metadata(
-                Some(&workspace_root.join("Cargo.toml")),
-                false,
-                false,
-                false,
-                &workspace_root,
-            )?;
-        }
-        Ok(())
+            if dry_run {
+                stderr.warn("not modifying the manifest due to dry run")?;
+            } else if !is_empty_workspace(&workspace_root.join("Cargo.toml"))? {
+                update_lockfile(&workspace_root, false, &mut stderr)?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
@@ -248,7 +366,9 @@ impl<W: WriteColor> Exclude<W> {
 pub struct Deactivate<W> {
     workspace_root: anyhow::Result<PathBuf>,
     paths: anyhow::Result<Vec<PathBuf>>,
+    glob_strategy: GlobStrategy,
     dry_run: bool,
+    backup: bool,
     stderr: W,
 }
 
@@ -260,7 +380,9 @@ impl Deactivate<NoColor<Sink>> {
         Self {
             workspace_root: ensure_absolute(workspace_root),
             paths: paths.into_iter().map(ensure_absolute).collect(),
+            glob_strategy: GlobStrategy::default(),
             dry_run: false,
+            backup: false,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -289,67 +411,87 @@ impl Deactivate<NoColor<Sink>> {
                         .to_owned())
                 }))
                 .collect(),
+            glob_strategy: GlobStrategy::default(),
             dry_run: false,
+            backup: false,
             stderr: NoColor::new(io::sink()),
         }
     }
 }
 
 impl<W: WriteColor> Deactivate<W> {
+    pub fn glob_strategy(self, glob_strategy: GlobStrategy) -> Self {
+        Self {
+            glob_strategy,
+            ..self
+        }
+    }
+
     pub fn dry_run(self, dry_run: bool) -> Self {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
     pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Deactivate<W2> {
         Deactivate {
             workspace_root: self.workspace_root,
             paths: self.paths,
+            glob_strategy: self.glob_strategy,
             dry_run: self.dry_run,
+            backup: self.backup,
             stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             mut stderr,
             workspace_root,
             paths,
+            glob_strategy,
             dry_run,
+            backup,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (workspace_root, paths) = (workspace_root?, paths?);
+            crate::fs::journal(&workspace_root, "deactivate");
+
+            let modified = paths.iter().try_fold(false, |acc, path| {
+                modify_members(
+                    &workspace_root,
+                    &[],
+                    &[],
+                    &[path],
+                    &[path],
+                    &[],
+                    &[],
+                    Some(glob_strategy),
+                    dry_run,
+                    &mut stderr,
+                )
+                .map(|p| acc | p)
+            })?;
+
+            if !modified {
+                stderr.warn("`workspace` unchanged")?;
+            }
 
-        let (workspace_root, paths) = (workspace_root?, paths?);
-
-        let modified = paths.iter().try_fold(false, |acc, path| {
-            modify_members(
-                &workspace_root,
-                &[],
-                &[],
-                &[path],
-                &[path],
-                dry_run,
-                &mut stderr,
-            )
-            .map(|p| acc | p)
-        })?;
-
-        if !modified {
-            stderr.warn("`workspace` unchanged")?;
-        }
+            stderr.dry_run_diffs()?;
 
-        if dry_run {
-            stderr.warn("not modifying the manifest due to dry run")?;
-        } else if !is_empty_workspace(&workspace_root.join("Cargo.toml"))? {
-            stderr.status("Updating", workspace_root.join("Cargo.lock").display())?;
-
-            cargo_metadata(
-                Some(&workspace_root.join("Cargo.toml")),
-                false,
-                false,
-                false,
-                &workspace_root,
-            )?;
-        }
-        Ok(())
+            if dry_run {
+                stderr.warn("not modifying the manifest due to dry run")?;
+            } else if !is_empty_workspace(&workspace_root.join("Cargo.toml"))? {
+                update_lockfile(&workspace_root, false, &mut stderr)?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
@@ -358,8 +500,12 @@ pub struct Focus<W> {
     workspace_root: anyhow::Result<PathBuf>,
     path: anyhow::Result<PathBuf>,
     dry_run: bool,
+    backup: bool,
     offline: bool,
     exclude: bool,
+    default_members: bool,
+    glob_strategy: GlobStrategy,
+    allow_dirty: bool,
     stderr: W,
 }
 
@@ -369,8 +515,12 @@ impl Focus<NoColor<Sink>> {
             workspace_root: ensure_absolute(workspace_root),
             path: ensure_absolute(path),
             dry_run: false,
+            backup: false,
             offline: false,
             exclude: false,
+            default_members: false,
+            glob_strategy: GlobStrategy::default(),
+            allow_dirty: false,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -381,6 +531,11 @@ impl<W: WriteColor> Focus<W> {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
     pub fn offline(self, offline: bool) -> Self {
         Self { offline, ..self }
     }
@@ -389,70 +544,548 @@ impl<W: WriteColor> Focus<W> {
         Self { exclude, ..self }
     }
 
+    /// Sets `workspace.default-members` to the package instead of shrinking `workspace.members`.
+    pub fn default_members(self, default_members: bool) -> Self {
+        Self {
+            default_members,
+            ..self
+        }
+    }
+
+    pub fn glob_strategy(self, glob_strategy: GlobStrategy) -> Self {
+        Self {
+            glob_strategy,
+            ..self
+        }
+    }
+
+    /// Focuses even if the root manifest or the lock file has uncommitted changes.
+    pub fn allow_dirty(self, allow_dirty: bool) -> Self {
+        Self {
+            allow_dirty,
+            ..self
+        }
+    }
+
     pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Focus<W2> {
         Focus {
             workspace_root: self.workspace_root,
             path: self.path,
             dry_run: self.dry_run,
+            backup: self.backup,
+            allow_dirty: self.allow_dirty,
             offline: self.offline,
             exclude: self.exclude,
+            default_members: self.default_members,
+            glob_strategy: self.glob_strategy,
             stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             workspace_root,
             path,
             dry_run,
+            backup,
+            allow_dirty,
             offline,
             exclude,
+            default_members,
+            glob_strategy,
             mut stderr,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
 
-        let (workspace_root, path) = (workspace_root?, path?);
+            let (workspace_root, path) = (workspace_root?, path?);
+            crate::fs::journal(&workspace_root, "focus");
 
-        let mut targets = vec![];
-        for entry in Walk::new(&workspace_root) {
-            match entry {
-                Ok(entry) => {
-                    if entry.path().ends_with("Cargo.toml") {
-                        let dir = entry.path().parent().expect("should not empty");
-                        if ![&*workspace_root, &*path].contains(&dir) {
-                            targets.push(dir.to_owned());
-                        }
-                    }
+            if !allow_dirty {
+                crate::git::ensure_clean(&workspace_files(&workspace_root), "focus")?;
+            }
+
+            let targets = find_packages(&workspace_root, &mut stderr)?;
+            let targets = targets
+                .iter()
+                .map(Deref::deref)
+                .filter(|&p| ![&*workspace_root, &*path].contains(&p))
+                .collect::<Vec<_>>();
+
+            let path_ = [&*path];
+            let (
+                add_to_workspace_exclude,
+                rm_from_workspace_members,
+                add_to_workspace_default_members,
+                rm_from_workspace_default_members,
+            ) = match (default_members, exclude) {
+                (true, _) => (&[][..], &[][..], &path_[..], &*targets),
+                (false, true) => (&*targets, &*targets, &[][..], &[][..]),
+                (false, false) => (&[][..], &*targets, &[][..], &[][..]),
+            };
+
+            let manifest_path = workspace_root.join("Cargo.toml");
+            let before = crate::fs::read_to_string(&manifest_path)?;
+
+            let modified = modify_members(
+                &workspace_root,
+                &[&path],
+                add_to_workspace_exclude,
+                rm_from_workspace_members,
+                &[&path],
+                add_to_workspace_default_members,
+                rm_from_workspace_default_members,
+                Some(glob_strategy),
+                dry_run,
+                &mut stderr,
+            )?;
+
+            stderr.dry_run_diffs()?;
+
+            if dry_run {
+                stderr.warn("not modifying `workspace` due to dry run")?;
+            } else {
+                if modified {
+                    FocusState::save(&workspace_root, before, &manifest_path)?;
                 }
-                Err(err) => stderr.warn(err)?,
+
+                update_lockfile(&workspace_root, offline, &mut stderr)?;
             }
+            Ok(())
+        })
+        .map_err(Into::into)
+    }
+}
+
+#[derive(Debug)]
+pub struct Unfocus<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    force: bool,
+    dry_run: bool,
+    backup: bool,
+    offline: bool,
+    stderr: W,
+}
+
+impl Unfocus<NoColor<Sink>> {
+    pub fn new(workspace_root: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+            force: false,
+            dry_run: false,
+            backup: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> Unfocus<W> {
+    /// Restores `workspace` even if the manifest has been modified since `focus`.
+    pub fn force(self, force: bool) -> Self {
+        Self { force, ..self }
+    }
+
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Unfocus<W2> {
+        Unfocus {
+            workspace_root: self.workspace_root,
+            force: self.force,
+            dry_run: self.dry_run,
+            backup: self.backup,
+            offline: self.offline,
+            stderr,
         }
-        let targets = targets.iter().map(Deref::deref).collect::<Vec<_>>();
+    }
 
-        modify_members(
-            &workspace_root,
-            &[&path],
-            if exclude { &targets } else { &[] },
-            &targets,
-            &[&path],
+    pub fn exec(self) -> crate::Result<()> {
+        let Self {
+            workspace_root,
+            force,
             dry_run,
-            &mut stderr,
-        )?;
+            backup,
+            offline,
+            mut stderr,
+        } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
 
-        if dry_run {
-            stderr.warn("not modifying `workspace` due to dry run")?;
-        } else {
-            stderr.status("Updating", workspace_root.join("Cargo.lock").display())?;
+            crate::fs::backup(backup);
 
-            cargo_metadata(
-                Some(&workspace_root.join("Cargo.toml")),
-                false,
-                false,
-                offline,
-                &workspace_root,
+            let workspace_root = workspace_root?;
+            crate::fs::journal(&workspace_root, "unfocus");
+            let manifest_path = workspace_root.join("Cargo.toml");
+            let state_path = FocusState::path(&workspace_root);
+
+            if !state_path.exists() {
+                bail!(
+                    "`{}` not found. run `cargo member focus` first",
+                    state_path.display(),
+                );
+            }
+            let FocusState { before, after } = crate::fs::read_json(&state_path)?;
+            let current = crate::fs::read_to_string(&manifest_path)?;
+
+            let restored = if current == after {
+                before
+            } else if force {
+                let before = before
+                    .parse::<toml_edit::DocumentMut>()
+                    .with_context(|| format!("failed to parse `{}`", state_path.display()))?;
+                let mut current = current.parse::<toml_edit::DocumentMut>().with_context(|| {
+                    format!(
+                        "failed to parse the TOML file at {}",
+                        manifest_path.display()
+                    )
+                })?;
+                current["workspace"] = before.at("workspace").clone();
+                current.to_string()
+            } else {
+                bail!(
+                    "`{}` has been modified since `focus`. enable `--force` to overwrite `workspace`",
+                    manifest_path.display(),
+                );
+            };
+
+            stderr.status(
+                "Restoring",
+                format!("`workspace` in `{}`", manifest_path.display()),
             )?;
+            if dry_run {
+                crate::fs::dry_run_diff(&manifest_path, &manifest_path, &current);
+            }
+            crate::fs::write(&manifest_path, restored, dry_run)?;
+            crate::fs::remove_file(&state_path, dry_run)?;
+
+            stderr.dry_run_diffs()?;
+
+            if dry_run {
+                stderr.warn("not modifying `workspace` due to dry run")?;
+            } else {
+                update_lockfile(&workspace_root, offline, &mut stderr)?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
+    }
+}
+
+/// The root manifest before and after `focus`, kept for `unfocus`.
+#[derive(Serialize, Deserialize, Debug)]
+struct FocusState {
+    before: String,
+    after: String,
+}
+
+impl FocusState {
+    fn path(workspace_root: &Path) -> PathBuf {
+        crate::fs::target_dir(workspace_root)
+            .join("cargo-member")
+            .join("focus.json")
+    }
+
+    /// Saves the state. If the workspace is already focused, the original `before` is kept so
+    /// that `unfocus` goes back to the unfocused workspace.
+    fn save(workspace_root: &Path, before: String, manifest_path: &Path) -> anyhow::Result<()> {
+        let path = Self::path(workspace_root);
+
+        let before = if path.exists() {
+            let prev = crate::fs::read_json::<Self>(&path)?;
+            if prev.after == before {
+                prev.before
+            } else {
+                before
+            }
+        } else {
+            before
+        };
+        let after = crate::fs::read_to_string(manifest_path)?;
+
+        crate::fs::create_dir_all(path.parent().expect("should have a parent"), false)?;
+        crate::fs::write(
+            &path,
+            serde_json::to_string(&Self { before, after })?,
+            false,
+        )
+    }
+}
+
+/// Packages moved to the trash by one `rm`, kept in `<entry>/trash.json` for `restore`.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Trash {
+    packages: Vec<TrashedPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TrashedPackage {
+    /// The original path.
+    path: PathBuf,
+    /// The name of the directory in the entry.
+    name: String,
+    member: bool,
+    default_member: bool,
+}
+
+impl Trash {
+    fn dir(workspace_root: &Path) -> PathBuf {
+        crate::fs::target_dir(workspace_root)
+            .join("cargo-member")
+            .join("trash")
+    }
+
+    /// A path for a new entry, named after the current time in milliseconds.
+    fn new_entry(workspace_root: &Path) -> anyhow::Result<PathBuf> {
+        let mut id = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        loop {
+            let entry = Self::dir(workspace_root).join(id.to_string());
+            if !entry.exists() {
+                break Ok(entry);
+            }
+            id += 1;
         }
-        Ok(())
     }
+
+    fn latest_entry(workspace_root: &Path) -> anyhow::Result<Option<PathBuf>> {
+        let dir = Self::dir(workspace_root);
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let latest = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter_map(|path| Some((path.file_name()?.to_str()?.parse::<u128>().ok()?, path)))
+            .max_by_key(|&(id, _)| id)
+            .map(|(_, path)| path);
+        Ok(latest)
+    }
+
+    /// Moves `path` into `entry`, recording whether it is in `workspace.{members, default-members}`.
+    fn put(
+        &mut self,
+        workspace_root: &Path,
+        entry: &Path,
+        path: &Path,
+        dry_run: bool,
+    ) -> anyhow::Result<PathBuf> {
+        let cargo_toml = crate::fs::read_toml_edit(workspace_root.join("Cargo.toml"))?;
+        let listed = |field: &str| {
+            cargo_toml
+                .at("workspace")
+                .at(field)
+                .as_array()
+                .into_iter()
+                .flat_map(toml_edit::Array::iter)
+                .flat_map(toml_edit::Value::as_str)
+                .any(|s| {
+                    workspace_root.join(s) == path
+                        || is_glob(s) && glob_matches(workspace_root, s, path)
+                })
+        };
+        let member = listed("members") && !listed("exclude");
+        let default_member = listed("default-members");
+
+        let file_name = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .with_context(|| format!("{:?} is not valid UTF-8", path))?;
+        let mut name = file_name.to_owned();
+        for i in 2.. {
+            if !self.packages.iter().any(|p| p.name == name) {
+                break;
+            }
+            name = format!("{}-{}", file_name, i);
+        }
+
+        if !entry.exists() {
+            crate::fs::create_dir_all(entry, dry_run)?;
+        }
+        let trashed = entry.join(&name);
+        crate::fs::rename(path, &trashed, dry_run)?;
+        self.packages.push(TrashedPackage {
+            path: path.to_owned(),
+            name,
+            member,
+            default_member,
+        });
+        Ok(trashed)
+    }
+
+    fn save(&self, entry: &Path, dry_run: bool) -> anyhow::Result<()> {
+        crate::fs::write(
+            entry.join("trash.json"),
+            serde_json::to_string_pretty(self)?,
+            dry_run,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct List<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    workspace_members: Option<Vec<PathBuf>>,
+    offline: bool,
+    stderr: W,
+}
+
+impl List<NoColor<Sink>> {
+    /// The members are read with `cargo metadata --no-deps` in `exec`.
+    pub fn new(workspace_root: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+            workspace_members: None,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            workspace_root: Ok(metadata.workspace_root.clone()),
+            workspace_members: Some(member_dirs(metadata)),
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> List<W> {
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> List<W2> {
+        List {
+            workspace_root: self.workspace_root,
+            workspace_members: self.workspace_members,
+            offline: self.offline,
+            stderr,
+        }
+    }
+
+    pub fn exec(self) -> crate::Result<Vec<ListedPackage>> {
+        (|| -> anyhow::Result<_> {
+            let Self {
+                workspace_root,
+                workspace_members,
+                offline,
+                mut stderr,
+            } = self;
+
+            let workspace_root = workspace_root?;
+            let workspace_members = match workspace_members {
+                Some(workspace_members) => workspace_members,
+                None => member_dirs(&cargo_metadata_no_deps(&workspace_root, offline)?),
+            };
+
+            let cargo_toml = crate::fs::read_toml_edit(workspace_root.join("Cargo.toml"))?;
+            let string_array = |field: &str| -> Option<Vec<String>> {
+                cargo_toml
+                    .at("workspace")
+                    .at(field)
+                    .as_array()
+                    .map(|array| {
+                        array
+                            .iter()
+                            .flat_map(toml_edit::Value::as_str)
+                            .map(ToOwned::to_owned)
+                            .collect()
+                    })
+            };
+            let exclude = string_array("exclude")
+                .unwrap_or_default()
+                .iter()
+                .map(|s| workspace_root.join(s))
+                .collect::<Vec<_>>();
+            let default_members = string_array("default-members");
+            let has_root_package = cargo_toml.at("package").is_table_like();
+
+            let mut packages = vec![];
+            for dir in find_packages(&workspace_root, &mut stderr)? {
+                if dir == workspace_root && !has_root_package {
+                    continue;
+                }
+
+                let name = crate::fs::read_toml_edit(dir.join("Cargo.toml"))?
+                    .at("package")
+                    .at("name")
+                    .as_str()
+                    .map(ToOwned::to_owned);
+
+                let status = if workspace_members.contains(&dir) {
+                    PackageStatus::Member
+                } else if exclude.iter().any(|e| dir.starts_with(e)) {
+                    PackageStatus::Excluded
+                } else {
+                    PackageStatus::Inactive
+                };
+
+                let default_member = status == PackageStatus::Member
+                    && match &default_members {
+                        Some(default_members) => default_members.iter().any(|d| {
+                            workspace_root.join(d) == dir
+                                || is_glob(d) && glob_matches(&workspace_root, d, &dir)
+                        }),
+                        None => !has_root_package || dir == workspace_root,
+                    };
+
+                let path = dir.strip_prefix(&workspace_root)?;
+                let path = if path == Path::new("") {
+                    ".".to_owned()
+                } else {
+                    path.to_str()
+                        .with_context(|| format!("{:?} is not valid UTF-8 path", path))?
+                        .to_owned()
+                };
+
+                packages.push(ListedPackage {
+                    path,
+                    name,
+                    status,
+                    default_member,
+                });
+            }
+            packages.sort_by(|p1, p2| p1.path.cmp(&p2.path));
+            Ok(packages)
+        })()
+        .map_err(Into::into)
+    }
+}
+
+/// A package found under the workspace root.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct ListedPackage {
+    /// Path relative to the workspace root.
+    pub path: String,
+    /// `package.name`. `None` for virtual manifests.
+    pub name: Option<String>,
+    pub status: PackageStatus,
+    /// Whether the package is built by `cargo build` at the workspace root.
+    pub default_member: bool,
+}
+
+/// Whether a package is in the workspace.
+#[derive(Serialize, IntoStaticStr, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum PackageStatus {
+    /// A member of the workspace.
+    Member,
+    /// Covered by `workspace.exclude`.
+    Excluded,
+    /// In neither `workspace.members` nor `workspace.exclude`.
+    Inactive,
 }
 
 #[derive(Debug)]
@@ -466,10 +1099,12 @@ pub struct New<W> {
     cargo_new_stderr_redirection: Stdio,
     offline: bool,
     dry_run: bool,
+    backup: bool,
     stderr: W,
 }
 
 impl New<NoColor<Sink>> {
+    #[allow(clippy::self_named_constructors)]
     pub fn new(possibly_empty_workspace_root: &Path, path: &Path) -> Self {
         Self {
             possibly_empty_workspace_root: ensure_absolute(possibly_empty_workspace_root),
@@ -481,6 +1116,7 @@ impl New<NoColor<Sink>> {
             cargo_new_stderr_redirection: Stdio::null(),
             offline: false,
             dry_run: false,
+            backup: false,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -533,6 +1169,11 @@ impl<W: WriteColor> New<W> {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
     pub fn stderr<W2: WriteColor>(self, stderr: W2) -> New<W2> {
         New {
             possibly_empty_workspace_root: self.possibly_empty_workspace_root,
@@ -544,11 +1185,12 @@ impl<W: WriteColor> New<W> {
             cargo_new_stderr_redirection: self.cargo_new_stderr_redirection,
             offline: self.offline,
             dry_run: self.dry_run,
+            backup: self.backup,
             stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             possibly_empty_workspace_root,
             path,
@@ -559,62 +1201,78 @@ impl<W: WriteColor> New<W> {
             cargo_new_stderr_redirection,
             offline,
             dry_run,
+            backup,
             mut stderr,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (possibly_empty_workspace_root, path) = (possibly_empty_workspace_root?, path?);
+            crate::fs::journal(&possibly_empty_workspace_root, "new");
+
+            Include::new(&possibly_empty_workspace_root, [&path])
+                .force(true)
+                .dry_run(dry_run)
+                .stderr(&mut stderr)
+                .exec()?;
+
+            stderr.dry_run_diffs()?;
+
+            if dry_run {
+                stderr.warn("not creating a new package due to dry run")?;
+            } else {
+                let cargo_exe =
+                    env::var_os("CARGO").with_context(|| "`$CARGO` should be present")?;
+
+                let args = Args::new()
+                    .arg("new")
+                    .option(cargo_new_registry.as_ref(), "--registry")
+                    .option(cargo_new_vcs.as_ref(), "--vcs")
+                    .flag(cargo_new_lib, "--lib")
+                    .option(cargo_new_name.as_ref(), "--name")
+                    .flag(offline, "--offline")
+                    .arg(&path);
+
+                crate::fs::track(&path)?;
+
+                let output = Command::new(&cargo_exe)
+                    .args(&args)
+                    .current_dir(&possibly_empty_workspace_root)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(cargo_new_stderr_redirection)
+                    .output()
+                    .with_context(|| {
+                        format!("failed to execute `{}`", cargo_exe.to_string_lossy())
+                    })?;
+
+                stderr.write_all(&output.stderr)?;
+
+                if !output.status.success() {
+                    return Err(Error::Cargo {
+                        args: args
+                            .0
+                            .iter()
+                            .map(|s| s.to_string_lossy().into_owned())
+                            .collect(),
+                        message: format!(
+                            "`{}{}` failed ({})",
+                            shell_escape::escape(cargo_exe.to_string_lossy()),
+                            args.0.iter().format_with("", |s, f| f(&format_args!(
+                                " {}",
+                                shell_escape::escape(s.to_string_lossy()),
+                            ))),
+                            output.status,
+                        ),
+                    }
+                    .into());
+                }
 
-        let (possibly_empty_workspace_root, path) = (possibly_empty_workspace_root?, path?);
-
-        Include::new(&possibly_empty_workspace_root, &[&path])
-            .force(true)
-            .dry_run(dry_run)
-            .stderr(&mut stderr)
-            .exec()?;
-
-        if dry_run {
-            stderr.warn("not creating a new package due to dry run")?;
-        } else {
-            let cargo_exe = env::var_os("CARGO").with_context(|| "`$CARGO` should be present")?;
-
-            let args = Args::new()
-                .arg("new")
-                .option(cargo_new_registry.as_ref(), "--registry")
-                .option(cargo_new_vcs.as_ref(), "--vcs")
-                .flag(cargo_new_lib, "--lib")
-                .option(cargo_new_name.as_ref(), "--name")
-                .flag(offline, "--offline")
-                .arg(&path);
-
-            let output = Command::new(&cargo_exe)
-                .args(&args)
-                .current_dir(&possibly_empty_workspace_root)
-                .stdin(Stdio::null())
-                .stdout(Stdio::null())
-                .stderr(cargo_new_stderr_redirection)
-                .output()
-                .with_context(|| format!("failed to execute `{}`", cargo_exe.to_string_lossy()))?;
-
-            stderr.write_all(&output.stderr)?;
-
-            if !output.status.success() {
-                bail!(
-                    "`{}{}` failed ({})",
-                    shell_escape::escape(cargo_exe.to_string_lossy()),
-                    args.0.iter().format_with("", |s, f| f(&format_args!(
-                        " {}",
-                        shell_escape::escape(s.to_string_lossy()),
-                    ))),
-                    output.status,
-                );
+                update_lockfile(&possibly_empty_workspace_root, offline, &mut stderr)?;
             }
-
-            stderr.status(
-                "Updating",
-                possibly_empty_workspace_root.join("Cargo.lock").display(),
-            )?;
-
-            cargo_metadata(None, false, false, offline, &possibly_empty_workspace_root)?;
-        }
-        Ok(())
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
@@ -677,12 +1335,48 @@ impl IntoIterator for Args {
     }
 }
 
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".jj", ".pijul", ".svn"];
+
+/// Files `Cp` skips regardless of ignore files: `target`, `target_dir` if it is in the package,
+/// VCS directories unless `include_vcs`, and the `exclude` globs.
+fn copy_overrides(
+    src_root: &Path,
+    target_dir: &Path,
+    exclude: &[String],
+    include_vcs: bool,
+) -> anyhow::Result<Override> {
+    let mut overrides = OverrideBuilder::new(src_root);
+    overrides.add("!/target/")?;
+    if let Ok(relative) = target_dir.strip_prefix(src_root) {
+        if let Some(relative) = relative.to_str().filter(|s| !s.is_empty()) {
+            overrides.add(&format!("!/{}/", glob::Pattern::escape(relative)))?;
+        }
+    }
+    if !include_vcs {
+        for dir in VCS_DIRS {
+            overrides.add(&format!("!/{}", dir))?;
+        }
+    }
+    for glob in exclude {
+        overrides
+            .add(&format!("!{}", glob))
+            .with_context(|| format!("invalid glob: {:?}", glob))?;
+    }
+    Ok(overrides.build()?)
+}
+
 #[derive(Debug)]
 pub struct Cp<W> {
     src: anyhow::Result<PathBuf>,
     dst: anyhow::Result<PathBuf>,
     dry_run: bool,
+    backup: bool,
     no_rename: bool,
+    inherit_strategy: InheritStrategy,
+    no_ignore: bool,
+    exclude: Vec<String>,
+    include_vcs: bool,
+    files: Option<BTreeSet<PathBuf>>,
     stderr: W,
 }
 
@@ -692,7 +1386,13 @@ impl Cp<NoColor<Sink>> {
             src: ensure_absolute(src),
             dst: ensure_absolute(dst),
             dry_run: false,
+            backup: false,
             no_rename: false,
+            inherit_strategy: InheritStrategy::default(),
+            no_ignore: false,
+            exclude: vec![],
+            include_vcs: false,
+            files: None,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -708,7 +1408,13 @@ impl Cp<NoColor<Sink>> {
             }),
             dst: ensure_absolute(dst),
             dry_run: false,
+            backup: false,
             no_rename: false,
+            inherit_strategy: InheritStrategy::default(),
+            no_ignore: false,
+            exclude: vec![],
+            include_vcs: false,
+            files: None,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -719,107 +1425,300 @@ impl<W: WriteColor> Cp<W> {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
     pub fn no_rename(self, no_rename: bool) -> Self {
         Self { no_rename, ..self }
     }
 
+    pub fn inherit_strategy(self, inherit_strategy: InheritStrategy) -> Self {
+        Self {
+            inherit_strategy,
+            ..self
+        }
+    }
+
+    /// Copies files ignored by `.gitignore`, `.ignore` and `.git/info/exclude` too.
+    pub fn no_ignore(self, no_ignore: bool) -> Self {
+        Self { no_ignore, ..self }
+    }
+
+    /// Skips files matching the gitignore-style globs, relative to the package root.
+    pub fn exclude<Ss: IntoIterator<Item = S>, S: AsRef<str>>(self, exclude: Ss) -> Self {
+        let exclude = exclude.into_iter().map(|s| s.as_ref().to_owned()).collect();
+        Self { exclude, ..self }
+    }
+
+    /// Copies VCS directories such as `.git` too.
+    pub fn include_vcs(self, include_vcs: bool) -> Self {
+        Self {
+            include_vcs,
+            ..self
+        }
+    }
+
+    /// Copies only `files`, relative to the package root, regardless of the ignore rules.
+    fn files(self, files: BTreeSet<PathBuf>) -> Self {
+        Self {
+            files: Some(files),
+            no_ignore: true,
+            ..self
+        }
+    }
+
     pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Cp<W2> {
         Cp {
             src: self.src,
             dst: self.dst,
             dry_run: self.dry_run,
+            backup: self.backup,
             no_rename: self.no_rename,
+            inherit_strategy: self.inherit_strategy,
+            no_ignore: self.no_ignore,
+            exclude: self.exclude,
+            include_vcs: self.include_vcs,
+            files: self.files,
             stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             mut stderr,
             src,
             dst,
             dry_run,
+            backup,
             no_rename,
+            inherit_strategy,
+            no_ignore,
+            exclude,
+            include_vcs,
+            files,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (src, dst) = (src?, dst?);
+
+            let dst = if dst.exists() {
+                dst.join(src.file_name().expect("should be absolute"))
+            } else {
+                dst
+            };
+
+            ensure!(!dst.exists(), "`{}` exists", dst.display());
+
+            let mut cargo_toml = crate::fs::read_toml_edit(src.join("Cargo.toml"))
+                .with_context(|| format!("`{}` does not seem to be a package", src.display()))?;
+            let orig_cargo_toml = cargo_toml.to_string();
+            if let Some(package) = cargo_toml["package"].as_table_mut() {
+                package.remove("workspace");
+                if !no_rename {
+                    let file_name = dst.file_name().expect("should exist");
+                    let file_name = file_name
+                        .to_str()
+                        .with_context(|| format!("{:?} is not valid UTF-8", file_name))?;
+                    package["name"] = toml_edit::value(file_name);
+                }
+            }
 
-        let (src, dst) = (src?, dst?);
-
-        let dst = if dst.exists() {
-            dst.join(src.file_name().expect("should be absolute"))
-        } else {
-            dst
-        };
-
-        ensure!(!dst.exists(), "`{}` exists", dst.display());
+            stderr.status(
+                "Copying",
+                format!("`{}` to `{}`", src.display(), dst.display()),
+            )?;
 
-        let mut cargo_toml = crate::fs::read_toml_edit(src.join("Cargo.toml"))
-            .with_context(|| format!("`{}` does not seem to be a package", src.display()))?;
-        if let Some(package) = cargo_toml["package"].as_table_mut() {
-            package.remove("workspace");
-            if !no_rename {
-                let file_name = dst.file_name().expect("should exist");
-                let file_name = file_name
-                    .to_str()
-                    .with_context(|| format!("{:?} is not valid UTF-8", file_name))?;
-                package["name"] = toml_edit::value(file_name);
+            for (key, old, new) in crate::manifest::rebase_paths(&mut cargo_toml, &src, &dst)? {
+                stderr.status("Rebasing", format!("`{}` from {:?} to {:?}", key, old, new))?;
             }
-        }
 
-        stderr.status(
-            "Copying",
-            format!("`{}` to `{}`", src.display(), dst.display()),
-        )?;
+            // As in Cargo, a package with its own `[workspace]` is the root of the workspace.
+            let src_workspace_root = if cargo_toml.at("workspace").is_none() {
+                find_workspace_root(&src)?
+            } else {
+                Some(src.clone())
+            };
+            let dst_workspace_root = find_workspace_root(&dst)?;
+            if let Some(root) = dst_workspace_root.as_ref().or(src_workspace_root.as_ref()) {
+                crate::fs::journal(root, "cp");
+            }
+            if let Some(src_workspace_root) = src_workspace_root
+                .as_ref()
+                .filter(|&root| Some(root) != dst_workspace_root.as_ref())
+            {
+                let src_workspace =
+                    crate::fs::read_toml_edit(src_workspace_root.join("Cargo.toml"))?;
+
+                match (inherit_strategy, &dst_workspace_root) {
+                    (InheritStrategy::Add, Some(dst_workspace_root)) => {
+                        let dst_manifest_path = dst_workspace_root.join("Cargo.toml");
+                        let orig_dst_workspace = crate::fs::read_to_string(&dst_manifest_path)?;
+                        let mut dst_workspace =
+                            orig_dst_workspace.parse::<toml_edit::DocumentMut>()?;
+                        let (added, conflicting) = crate::manifest::add_inherited(
+                            &mut cargo_toml,
+                            &src_workspace,
+                            src_workspace_root,
+                            &mut dst_workspace,
+                            dst_workspace_root,
+                        )?;
+                        for field in &conflicting {
+                            stderr.warn(format!(
+                                "`{}` differs between `{}` and `{}`. keeping the latter",
+                                field,
+                                src_workspace_root.join("Cargo.toml").display(),
+                                dst_manifest_path.display(),
+                            ))?;
+                        }
+                        for field in &added {
+                            stderr.status("Adding", format!("`{}`", field))?;
+                        }
+                        if !added.is_empty() {
+                            let dst_workspace = dst_workspace.to_string();
+                            if dry_run {
+                                crate::fs::dry_run_diff(
+                                    &dst_manifest_path,
+                                    &dst_manifest_path,
+                                    &orig_dst_workspace,
+                                );
+                            }
+                            crate::fs::write(&dst_manifest_path, dst_workspace, dry_run)?;
+                        }
+                    }
+                    _ => {
+                        for field in crate::manifest::inline_inherited(
+                            &mut cargo_toml,
+                            &src_workspace,
+                            src_workspace_root,
+                            &dst,
+                        )? {
+                            stderr.status("Inlining", format!("`{}`", field))?;
+                        }
+                    }
+                }
+            }
 
-        let src_root = src;
-        for src in WalkBuilder::new(&src_root).hidden(false).build() {
-            match src {
-                Ok(src) => {
-                    let src = src.path();
-                    if !(src.is_dir()
-                        || src == src_root.join("Cargo.toml")
-                        || src.starts_with(src_root.join(".git")))
-                    {
-                        let dst = dst.join(src.strip_prefix(&src_root)?);
+            let (src_root, dst_root) = (src, &dst);
+
+            // Ignore files are read the same way whether or not the package is inside a Git
+            // repository.
+            let target_dir =
+                crate::fs::target_dir(src_workspace_root.as_deref().unwrap_or(&src_root));
+            let overrides = copy_overrides(&src_root, &target_dir, &exclude, include_vcs)?;
+
+            // Applied at the end so that read-only directories can still be filled.
+            let mut dir_permissions = vec![];
+
+            for src in WalkBuilder::new(&src_root)
+                .standard_filters(!no_ignore)
+                .hidden(false)
+                .git_global(false)
+                .require_git(false)
+                .overrides(overrides)
+                .sort_by_file_name(Ord::cmp)
+                .build()
+            {
+                match src {
+                    Ok(entry) => {
+                        let src = entry.path();
+                        if src == src_root.join("Cargo.toml") {
+                            continue;
+                        }
+                        let relative = src.strip_prefix(&src_root)?;
+                        let dst = dst.join(relative);
+
+                        // Directories are created even if empty. Symbolic links are not followed.
+                        if entry.file_type().is_some_and(|t| t.is_dir()) {
+                            if !dst.exists() {
+                                let permissions = std::fs::metadata(src)
+                                    .with_context(|| format!("failed to read `{}`", src.display()))?
+                                    .permissions();
+                                dir_permissions.push((dst.clone(), permissions));
+                            }
+                            if files.is_none() && !dst.exists() {
+                                crate::fs::create_dir_all(&dst, dry_run)?;
+                            }
+                            continue;
+                        }
+                        if files
+                            .as_ref()
+                            .is_some_and(|files| !files.contains(relative))
+                        {
+                            continue;
+                        }
                         if let Some(parent) = dst.parent() {
                             if !parent.exists() {
                                 crate::fs::create_dir_all(parent, dry_run)?;
                             }
                         }
-                        crate::fs::copy(src, dst, dry_run)?;
+                        if entry.path_is_symlink() {
+                            let target = rebase_symlink(src, &src_root, dst_root)?;
+                            if dry_run {
+                                stderr.status(
+                                    "+",
+                                    format!("{} -> {}", relative.display(), target.display()),
+                                )?;
+                            }
+                            crate::fs::symlink(target, dst, dry_run)?;
+                        } else {
+                            if dry_run {
+                                stderr.status("+", relative.display())?;
+                            }
+                            crate::fs::copy(src, dst, dry_run)?;
+                        }
                     }
+                    Err(err) => stderr.warn(err)?,
                 }
-                Err(err) => stderr.warn(err)?,
             }
-        }
 
-        crate::fs::write(dst.join("Cargo.toml"), cargo_toml.to_string(), dry_run)?;
+            let cargo_toml = cargo_toml.to_string();
+            if dry_run {
+                crate::fs::dry_run_diff(
+                    &dst.join("Cargo.toml"),
+                    &src_root.join("Cargo.toml"),
+                    &orig_cargo_toml,
+                );
+            }
+            crate::fs::write(dst.join("Cargo.toml"), cargo_toml, dry_run)?;
 
-        if let [_, dst_workspace_root] = &*dst
-            .ancestors()
-            .filter(|d| d.join("Cargo.toml").exists())
-            .collect::<Vec<_>>()
-        {
-            stderr.status_with_color(
-                "Found",
-                format!("workspace at {}", dst_workspace_root.display()),
-                termcolor::Color::Cyan,
-            )?;
+            for (dir, permissions) in dir_permissions.into_iter().rev() {
+                if dry_run || dir.is_dir() {
+                    crate::fs::set_permissions(dir, permissions, dry_run)?;
+                }
+            }
 
-            modify_members(
-                dst_workspace_root,
-                &[&dst],
-                &[],
-                &[],
-                &[&dst],
-                dry_run,
-                &mut stderr,
-            )?;
-        }
+            if let Some(dst_workspace_root) = &dst_workspace_root {
+                stderr.status_with_color(
+                    "Found",
+                    format!("workspace at {}", dst_workspace_root.display()),
+                    termcolor::Color::Cyan,
+                )?;
 
-        if dry_run {
-            stderr.warn("not copying due to dry run")?;
-        }
-        Ok(())
+                modify_members(
+                    dst_workspace_root,
+                    &[&dst],
+                    &[],
+                    &[],
+                    &[&dst],
+                    &[],
+                    &[],
+                    None,
+                    dry_run,
+                    &mut stderr,
+                )?;
+            }
+
+            stderr.dry_run_diffs()?;
+
+            if dry_run {
+                stderr.warn("not copying due to dry run")?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
@@ -827,12 +1726,19 @@ impl<W: WriteColor> Cp<W> {
 pub struct Rm<W> {
     workspace_root: anyhow::Result<PathBuf>,
     paths: anyhow::Result<Vec<PathBuf>>,
+    reverse_dependencies: Option<Vec<ReverseDependency>>,
     force: bool,
     dry_run: bool,
+    backup: bool,
+    allow_dirty: bool,
+    trash: bool,
+    offline: bool,
     stderr: W,
 }
 
 impl Rm<NoColor<Sink>> {
+    /// Other workspace members depending on the packages are found with `cargo metadata` in
+    /// `exec`.
     pub fn new<Ps: IntoIterator<Item = P>, P: AsRef<Path>>(
         workspace_root: &Path,
         paths: Ps,
@@ -840,8 +1746,13 @@ impl Rm<NoColor<Sink>> {
         Self {
             workspace_root: ensure_absolute(workspace_root),
             paths: paths.into_iter().map(ensure_absolute).collect(),
+            reverse_dependencies: None,
             force: false,
             dry_run: false,
+            backup: false,
+            allow_dirty: false,
+            trash: false,
+            offline: false,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -856,22 +1767,34 @@ impl Rm<NoColor<Sink>> {
         paths: Ps,
         specs: Ss,
     ) -> Self {
+        let paths = paths
+            .into_iter()
+            .map(ensure_absolute)
+            .chain(specs.into_iter().map(|spec| {
+                let member = metadata.query_for_member(Some(spec.as_ref()))?;
+                Ok(member
+                    .manifest_path
+                    .parent()
+                    .expect(r#"`manifest_path` should end with "Cargo.toml""#)
+                    .to_owned())
+            }))
+            .collect::<anyhow::Result<Vec<_>>>();
+
+        let reverse_dependencies = match &paths {
+            Ok(paths) => Some(ReverseDependency::find(metadata, paths)),
+            Err(_) => Some(vec![]),
+        };
+
         Self {
             workspace_root: Ok(metadata.workspace_root.clone()),
-            paths: paths
-                .into_iter()
-                .map(ensure_absolute)
-                .chain(specs.into_iter().map(|spec| {
-                    let member = metadata.query_for_member(Some(spec.as_ref()))?;
-                    Ok(member
-                        .manifest_path
-                        .parent()
-                        .expect(r#"`manifest_path` should end with "Cargo.toml""#)
-                        .to_owned())
-                }))
-                .collect(),
+            paths,
+            reverse_dependencies,
             force: false,
             dry_run: false,
+            backup: false,
+            allow_dirty: false,
+            trash: false,
+            offline: false,
             stderr: NoColor::new(io::sink()),
         }
     }
@@ -886,177 +1809,2198 @@ impl<W: WriteColor> Rm<W> {
         Self { dry_run, ..self }
     }
 
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
+    /// Removes the packages even if they, the root manifest or the lock file have uncommitted
+    /// changes.
+    pub fn allow_dirty(self, allow_dirty: bool) -> Self {
+        Self {
+            allow_dirty,
+            ..self
+        }
+    }
+
+    /// Moves the packages to `<target dir>/cargo-member/trash/<ID>` instead of deleting them, so
+    /// that `Restore` can put them back.
+    pub fn trash(self, trash: bool) -> Self {
+        Self { trash, ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    /// Skips looking for dependents, for callers that have checked them or rewritten them.
+    pub(crate) fn reverse_dependencies(self, reverse_dependencies: Vec<ReverseDependency>) -> Self {
+        Self {
+            reverse_dependencies: Some(reverse_dependencies),
+            ..self
+        }
+    }
+
     pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Rm<W2> {
         Rm {
             stderr,
             workspace_root: self.workspace_root,
             paths: self.paths,
+            reverse_dependencies: self.reverse_dependencies,
             force: self.force,
             dry_run: self.dry_run,
+            backup: self.backup,
+            allow_dirty: self.allow_dirty,
+            trash: self.trash,
+            offline: self.offline,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
             mut stderr,
             workspace_root,
             paths,
+            reverse_dependencies,
             force,
             dry_run,
+            backup,
+            allow_dirty,
+            trash,
+            offline,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (workspace_root, paths) = (workspace_root?, paths?);
+            crate::fs::journal(&workspace_root, "rm");
+
+            if !force {
+                let reverse_dependencies = match reverse_dependencies {
+                    Some(reverse_dependencies) => reverse_dependencies,
+                    None => {
+                        let metadata = cargo_metadata(
+                            Some(&workspace_root.join("Cargo.toml")),
+                            dry_run,
+                            dry_run,
+                            offline,
+                            &workspace_root,
+                        )?;
+                        ReverseDependency::find(&metadata, &paths)
+                    }
+                };
+                ReverseDependency::ensure_none(&reverse_dependencies, &paths, "remove")?;
+            }
+            if !allow_dirty {
+                let mut affected = paths.clone();
+                affected.extend_from_slice(&workspace_files(&workspace_root));
+                crate::git::ensure_clean(&affected, "remove")?;
+            }
 
-        let (workspace_root, paths) = (workspace_root?, paths?);
+            let mut trash = if trash {
+                Some((Trash::new_entry(&workspace_root)?, Trash::default()))
+            } else {
+                None
+            };
+
+            let modified = paths.iter().try_fold(false, |acc, path| {
+                if !(force || path.join("Cargo.toml").exists()) {
+                    return Err(Error::NotAPackage {
+                        path: path.clone(),
+                        action: "remove",
+                    }
+                    .into());
+                }
+                if let Some((entry, trash)) = &mut trash {
+                    let trashed = trash.put(&workspace_root, entry, path, dry_run)?;
+                    stderr.status(
+                        "Trashing",
+                        format!("`{}` to `{}`", path.display(), trashed.display()),
+                    )?;
+                } else {
+                    stderr.status_with_color(
+                        "Removing",
+                        format!("directory `{}`", path.display()),
+                        termcolor::Color::Red,
+                    )?;
+                    if dry_run && path.exists() {
+                        for file in WalkBuilder::new(path)
+                            .standard_filters(false)
+                            .sort_by_file_name(Ord::cmp)
+                            .build()
+                        {
+                            let file = file?;
+                            if !file.path().is_dir() {
+                                stderr.status_with_color(
+                                    "-",
+                                    file.path().strip_prefix(path)?.display(),
+                                    termcolor::Color::Red,
+                                )?;
+                            }
+                        }
+                    }
+                    crate::fs::remove_dir_all(path, dry_run)?;
+                }
+                modify_members(
+                    &workspace_root,
+                    &[],
+                    &[],
+                    &[path],
+                    &[path],
+                    &[],
+                    &[],
+                    None,
+                    dry_run,
+                    &mut stderr,
+                )
+                .map(|p| acc | p)
+            })?;
+
+            if let Some((entry, trash)) = trash {
+                trash.save(&entry, dry_run)?;
+            }
 
-        let modified = paths.iter().try_fold(false, |acc, path| {
-            if !(force || path.join("Cargo.toml").exists()) {
-                return Err(
-                    anyhow!("`{}` does not exist", path.join("Cargo.toml").display()).context(
-                        format!(
-                            "`{}` does not seem to be a package. enable `--force` to remove",
-                            path.display(),
-                        ),
-                    ),
-                );
+            if !modified {
+                stderr.warn("`workspace` unchanged")?;
             }
-            stderr.status_with_color(
-                "Removing",
-                format!("directory `{}`", path.display()),
-                termcolor::Color::Red,
-            )?;
-            crate::fs::remove_dir_all(path, dry_run)?;
-            modify_members(
-                &workspace_root,
-                &[],
-                &[],
-                &[path],
-                &[path],
-                dry_run,
-                &mut stderr,
-            )
-            .map(|p| acc | p)
-        })?;
 
-        if !modified {
-            stderr.warn("`workspace` unchanged")?;
-        }
+            stderr.dry_run_diffs()?;
 
-        if dry_run {
-            stderr.warn("not modifying the manifest due to dry run")?;
-        } else if !is_empty_workspace(&workspace_root.join("Cargo.toml"))? {
-            stderr.status("Updating", workspace_root.join("Cargo.lock").display())?;
-
-            cargo_metadata(
-                Some(&workspace_root.join("Cargo.toml")),
-                false,
-                false,
-                false,
-                &workspace_root,
-            )?;
-        }
-        Ok(())
+            if dry_run {
+                stderr.warn("not modifying the manifest due to dry run")?;
+            } else if !is_empty_workspace(&workspace_root.join("Cargo.toml"))? {
+                update_lockfile(&workspace_root, false, &mut stderr)?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
 #[derive(Debug)]
-pub struct Mv<W> {
+pub struct Restore<W> {
     workspace_root: anyhow::Result<PathBuf>,
-    src: anyhow::Result<PathBuf>,
-    dst: anyhow::Result<PathBuf>,
+    entry: Option<String>,
     dry_run: bool,
-    no_rename: bool,
+    backup: bool,
+    offline: bool,
     stderr: W,
 }
 
-impl Mv<NoColor<Sink>> {
-    pub fn new(workspace_root: &Path, src: &Path, dst: &Path) -> Self {
+impl Restore<NoColor<Sink>> {
+    pub fn new(workspace_root: &Path) -> Self {
         Self {
             workspace_root: ensure_absolute(workspace_root),
-            src: ensure_absolute(src),
-            dst: ensure_absolute(dst),
+            entry: None,
             dry_run: false,
-            no_rename: false,
+            backup: false,
+            offline: false,
             stderr: NoColor::new(io::sink()),
         }
     }
+}
 
-    pub fn from_metadata(metadata: &Metadata, src: &str, dst: &Path) -> Self {
+impl<W: WriteColor> Restore<W> {
+    /// The ID of the trash entry to restore. Defaults to the latest one.
+    pub fn entry(self, entry: Option<&str>) -> Self {
         Self {
-            workspace_root: Ok(metadata.workspace_root.clone()),
-            src: metadata.query_for_member(Some(src)).map(|member| {
-                member
-                    .manifest_path
-                    .parent()
-                    .expect(r#"`manifest_path` should end with "Cargo.toml""#)
-                    .to_owned()
-            }),
-            dst: ensure_absolute(dst),
-            dry_run: false,
-            no_rename: false,
-            stderr: NoColor::new(io::sink()),
+            entry: entry.map(ToOwned::to_owned),
+            ..self
         }
     }
-}
 
-impl<W: WriteColor> Mv<W> {
     pub fn dry_run(self, dry_run: bool) -> Self {
         Self { dry_run, ..self }
     }
 
-    pub fn no_rename(self, no_rename: bool) -> Self {
-        Self { no_rename, ..self }
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
     }
 
-    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Mv<W2> {
-        Mv {
-            stderr,
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Restore<W2> {
+        Restore {
             workspace_root: self.workspace_root,
-            src: self.src,
-            dst: self.dst,
+            entry: self.entry,
             dry_run: self.dry_run,
-            no_rename: self.no_rename,
+            backup: self.backup,
+            offline: self.offline,
+            stderr,
         }
     }
 
-    pub fn exec(self) -> anyhow::Result<()> {
+    pub fn exec(self) -> crate::Result<()> {
         let Self {
-            mut stderr,
             workspace_root,
-            src,
-            dst,
+            entry,
             dry_run,
-            no_rename,
+            backup,
+            offline,
+            mut stderr,
         } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let workspace_root = workspace_root?;
+            crate::fs::journal(&workspace_root, "restore");
+
+            let entry = match entry {
+                Some(entry) => Trash::dir(&workspace_root).join(entry),
+                None => Trash::latest_entry(&workspace_root)?.with_context(|| {
+                    format!(
+                        "nothing to restore in `{}`",
+                        Trash::dir(&workspace_root).display(),
+                    )
+                })?,
+            };
+            let trash = crate::fs::read_json::<Trash>(entry.join("trash.json"))?;
+
+            // `mv --trash` leaves a package with the same name in the workspace.
+            let names = cargo_metadata_no_deps(&workspace_root, offline)?
+                .packages
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>();
+            for package in trash.packages.iter().filter(|p| p.member) {
+                let manifest_path = entry.join(&package.name).join("Cargo.toml");
+                let cargo_toml = crate::fs::read_toml_edit(&manifest_path)?;
+                if let Some(name) = cargo_toml.at("package").at("name").as_str() {
+                    ensure!(
+                        !names.iter().any(|n| n == name),
+                        "cannot restore `{}`: the workspace already has a member named `{}`",
+                        package.path.display(),
+                        name,
+                    );
+                }
+            }
 
-        let (workspace_root, src, dst) = (workspace_root?, src?, dst?);
-
-        Cp::new(&src, &dst)
-            .dry_run(dry_run)
-            .no_rename(no_rename)
-            .stderr(&mut stderr)
-            .exec()?;
-
-        Rm::new(&workspace_root, &[src])
-            .dry_run(dry_run)
-            .stderr(stderr)
-            .exec()
+            for package in &trash.packages {
+                ensure!(
+                    !package.path.exists(),
+                    "`{}` exists",
+                    package.path.display()
+                );
+                stderr.status("Restoring", format!("`{}`", package.path.display()))?;
+                if let Some(parent) = package.path.parent() {
+                    if !parent.exists() {
+                        crate::fs::create_dir_all(parent, dry_run)?;
+                    }
+                }
+                crate::fs::rename(entry.join(&package.name), &package.path, dry_run)?;
+            }
+            crate::fs::remove_file(entry.join("trash.json"), dry_run)?;
+            crate::fs::remove_dir(&entry, dry_run)?;
+
+            // In dry run, the packages are not back yet.
+            for default_member in [true, false] {
+                let paths = trash
+                    .packages
+                    .iter()
+                    .filter(|p| p.member && p.default_member == default_member)
+                    .map(|p| &p.path)
+                    .collect::<Vec<_>>();
+                if !paths.is_empty() {
+                    Include::new(&workspace_root, paths)
+                        .force(dry_run)
+                        .default_member(if default_member { Some(true) } else { None })
+                        .seed_default_members(false)
+                        .dry_run(dry_run)
+                        .backup(backup)
+                        .offline(offline)
+                        .stderr(&mut stderr)
+                        .exec()?;
+                }
+            }
+            stderr.dry_run_diffs()?;
+            Ok(())
+        })
+        .map_err(Into::into)
     }
 }
 
-fn ensure_absolute(path: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
-    let path = path.as_ref();
-    ensure!(path.is_absolute(), "must be absolute: {}", path.display());
-    Ok(path.to_owned())
+#[derive(Debug)]
+pub struct Undo<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    force: bool,
+    dry_run: bool,
+    offline: bool,
+    stderr: W,
 }
 
-fn is_empty_workspace(manifest_path: &Path) -> anyhow::Result<bool> {
-    return {
-        let CargoToml { workspace, package } = crate::fs::read_toml(manifest_path)?;
-        Ok(!workspace.members.is_empty() || package.is_some())
-    };
-
-    #[derive(Deserialize)]
-    struct CargoToml {
-        #[serde(default)]
+impl Undo<NoColor<Sink>> {
+    pub fn new(workspace_root: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+            force: false,
+            dry_run: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> Undo<W> {
+    /// Overwrites files even if they have been modified since the command.
+    pub fn force(self, force: bool) -> Self {
+        Self { force, ..self }
+    }
+
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Undo<W2> {
+        Undo {
+            workspace_root: self.workspace_root,
+            force: self.force,
+            dry_run: self.dry_run,
+            offline: self.offline,
+            stderr,
+        }
+    }
+
+    /// Reverts the latest command in the journal and removes it from the journal.
+    pub fn exec(self) -> crate::Result<()> {
+        let Self {
+            workspace_root,
+            force,
+            dry_run,
+            offline,
+            mut stderr,
+        } = self;
+        return crate::fs::transaction(&mut stderr, |mut stderr| {
+            let workspace_root = workspace_root?;
+
+            let mut entries = crate::journal::read(&workspace_root)?;
+            let JournalEntry {
+                id,
+                operation,
+                modified,
+                lockfiles,
+                unrecorded,
+                created,
+                removed,
+                moved,
+            } = entries.pop().with_context(|| {
+                format!(
+                    "nothing to undo in `{}`",
+                    crate::journal::path(&workspace_root).display(),
+                )
+            })?;
+
+            if let Some(removed) = removed.first() {
+                bail!(
+                    "`{}` was deleted by `{}` and cannot be brought back. enable `--trash` to make \
+                     removals undoable",
+                    removed.display(),
+                    operation,
+                );
+            }
+            if let Some(unrecorded) = unrecorded.first() {
+                bail!(
+                    "`{}` was modified by `{}` and cannot be restored",
+                    unrecorded.display(),
+                    operation,
+                );
+            }
+            if !force {
+                for ModifiedFile { path, after, .. } in &modified {
+                    let current = if path.exists() {
+                        Some(crate::fs::read_to_string(path)?)
+                    } else {
+                        None
+                    };
+                    ensure!(
+                        current == *after,
+                        "`{}` has been changed since `{}`. enable `--force` to overwrite it",
+                        path.display(),
+                        operation,
+                    );
+                }
+            }
+
+            stderr.status("Undoing", format!("`{}` ({})", operation, id))?;
+
+            for MovedPath { from, to } in moved.iter().rev() {
+                ensure!(!from.exists(), "`{}` exists", from.display());
+                stderr.status(
+                    "Moving",
+                    format!("`{}` back to `{}`", to.display(), from.display()),
+                )?;
+                if let Some(parent) = from.parent() {
+                    if !parent.exists() {
+                        crate::fs::create_dir_all(parent, dry_run)?;
+                    }
+                }
+                crate::fs::rename(to, from, dry_run)?;
+            }
+
+            for ModifiedFile { path, before, .. } in &modified {
+                stderr.status("Restoring", format!("`{}`", path.display()))?;
+                if dry_run && path.exists() {
+                    let current = crate::fs::read_to_string(path)?;
+                    crate::fs::dry_run_diff(path, path, &current);
+                }
+                if let Some(parent) = path.parent() {
+                    if !parent.exists() {
+                        crate::fs::create_dir_all(parent, dry_run)?;
+                    }
+                }
+                crate::fs::write(path, before, dry_run)?;
+            }
+
+            // Directories are removed only if everything in them was created by the command.
+            let journal_path = crate::journal::path(&workspace_root);
+            let created_set = created.iter().map(Deref::deref).collect::<HashSet<_>>();
+            let is_dir = |path: &Path| path.symlink_metadata().is_ok_and(|m| m.is_dir());
+            let mut removable_dirs = vec![];
+            for path in &created {
+                if is_dir(path) && !removable_dirs.iter().any(|d| path.starts_with(d)) {
+                    if only_created(path, &created_set)? {
+                        removable_dirs.push(path);
+                    } else if !journal_path.starts_with(path) {
+                        stderr.warn(format!(
+                            "keeping `{}` as it contains files created afterwards",
+                            path.display(),
+                        ))?;
+                    }
+                }
+            }
+            for path in created.iter().rev() {
+                if path.symlink_metadata().is_err()
+                    || removable_dirs
+                        .iter()
+                        .any(|d| path != *d && path.starts_with(d))
+                {
+                    continue;
+                }
+                let is_dir = is_dir(path);
+                if is_dir && !removable_dirs.contains(&path) {
+                    continue;
+                }
+                stderr.status_with_color(
+                    "Removing",
+                    format!("`{}`", path.display()),
+                    termcolor::Color::Red,
+                )?;
+                if is_dir {
+                    crate::fs::remove_dir_all(path, dry_run)?;
+                } else {
+                    crate::fs::remove_file(path, dry_run)?;
+                }
+            }
+
+            stderr.dry_run_diffs()?;
+            if !dry_run {
+                // Lock files next to restored manifests may have been updated by later commands.
+                let roots = lockfiles
+                    .iter()
+                    .chain(
+                        modified
+                            .iter()
+                            .map(|ModifiedFile { path, .. }| path)
+                            .filter(|path| path.ends_with("Cargo.toml")),
+                    )
+                    .flat_map(|path| path.parent())
+                    .filter(|root| {
+                        root.join("Cargo.toml").exists() && root.join("Cargo.lock").exists()
+                    })
+                    .unique()
+                    .collect::<Vec<_>>();
+                for root in roots {
+                    update_lockfile(root, offline, &mut stderr)?;
+                }
+            }
+            crate::journal::write(&workspace_root, &entries, dry_run)?;
+            Ok(())
+        })
+        .map_err(Into::into);
+
+        fn only_created(dir: &Path, created: &HashSet<&Path>) -> anyhow::Result<bool> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                let ok = if path.symlink_metadata()?.is_dir() {
+                    only_created(&path, created)?
+                } else {
+                    created.contains(&*path)
+                };
+                if !ok {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Log {
+    workspace_root: anyhow::Result<PathBuf>,
+}
+
+impl Log {
+    pub fn new(workspace_root: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+        }
+    }
+
+    /// Returns the recorded commands, oldest first.
+    pub fn exec(self) -> crate::Result<Vec<JournalEntry>> {
+        let workspace_root = self.workspace_root?;
+        crate::journal::read(&workspace_root).map_err(Into::into)
+    }
+}
+
+/// A workspace member depending on a package to be removed.
+#[derive(Debug)]
+struct ReverseDependency {
+    dependency: PathBuf,
+    dependency_name: String,
+    dependent: String,
+    dependent_dir: PathBuf,
+    kind: String,
+}
+
+impl ReverseDependency {
+    fn find(metadata: &Metadata, paths: &[PathBuf]) -> Vec<Self> {
+        let resolve = match &metadata.resolve {
+            Some(resolve) => resolve,
+            None => return vec![],
+        };
+
+        let package_dir = |id| -> _ {
+            metadata[id]
+                .manifest_path
+                .parent()
+                .expect(r#"`manifest_path` should end with "Cargo.toml""#)
+        };
+
+        let targets = metadata
+            .workspace_members
+            .iter()
+            .filter(|id| paths.iter().any(|p| p == package_dir(id)))
+            .collect::<Vec<_>>();
+
+        resolve
+            .nodes
+            .iter()
+            .filter(|n| metadata.workspace_members.contains(&n.id) && !targets.contains(&&n.id))
+            .flat_map(|node| {
+                node.deps
+                    .iter()
+                    .filter(|d| targets.contains(&&d.pkg))
+                    .flat_map(move |dep| {
+                        let kinds = if dep.dep_kinds.is_empty() {
+                            vec!["normal".to_owned()]
+                        } else {
+                            dep.dep_kinds
+                                .iter()
+                                .map(|DepKindInfo { kind, target, .. }| {
+                                    let kind = match kind {
+                                        DependencyKind::Development => "dev",
+                                        DependencyKind::Build => "build",
+                                        _ => "normal",
+                                    };
+                                    match target {
+                                        Some(target) => format!("{}, {}", kind, target),
+                                        None => kind.to_owned(),
+                                    }
+                                })
+                                .collect()
+                        };
+                        kinds.into_iter().map(move |kind| Self {
+                            dependency: package_dir(&dep.pkg).to_owned(),
+                            dependency_name: metadata[&dep.pkg].name.clone(),
+                            dependent: metadata[&node.id].name.clone(),
+                            dependent_dir: package_dir(&node.id).to_owned(),
+                            kind,
+                        })
+                    })
+            })
+            .collect()
+    }
+
+    /// Fails if any of `paths` is required by another workspace member.
+    fn ensure_none(
+        reverse_dependencies: &[Self],
+        paths: &[PathBuf],
+        action: &str,
+    ) -> anyhow::Result<()> {
+        for path in paths {
+            let dependents = reverse_dependencies
+                .iter()
+                .filter(|d| d.dependency == *path)
+                .collect::<Vec<_>>();
+            if let [first, ..] = *dependents {
+                return Err(anyhow!(
+                    "{}",
+                    dependents.iter().format_with("\n", |d, f| f(&format_args!(
+                        "`{}` ({})",
+                        d.dependent, d.kind,
+                    ))),
+                )
+                .context(format!(
+                    "package `{}` is required by other workspace members. enable `--force` to {}",
+                    first.dependency_name, action,
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Extract<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    src: anyhow::Result<PathBuf>,
+    dst: anyhow::Result<PathBuf>,
+    reverse_dependencies: Option<Vec<ReverseDependency>>,
+    force: bool,
+    dry_run: bool,
+    backup: bool,
+    allow_dirty: bool,
+    offline: bool,
+    stderr: W,
+}
+
+impl Extract<NoColor<Sink>> {
+    pub fn new(workspace_root: &Path, src: &Path, dst: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+            src: ensure_absolute(src),
+            dst: ensure_absolute(dst),
+            reverse_dependencies: None,
+            force: false,
+            dry_run: false,
+            backup: false,
+            allow_dirty: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+
+    pub fn from_metadata(metadata: &Metadata, src: &str, dst: &Path) -> Self {
+        let src = metadata.query_for_member(Some(src)).map(|member| {
+            member
+                .manifest_path
+                .parent()
+                .expect(r#"`manifest_path` should end with "Cargo.toml""#)
+                .to_owned()
+        });
+
+        let reverse_dependencies = match &src {
+            Ok(src) => ReverseDependency::find(metadata, slice::from_ref(src)),
+            Err(_) => vec![],
+        };
+
+        Self {
+            workspace_root: Ok(metadata.workspace_root.clone()),
+            src,
+            dst: ensure_absolute(dst),
+            reverse_dependencies: Some(reverse_dependencies),
+            force: false,
+            dry_run: false,
+            backup: false,
+            allow_dirty: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> Extract<W> {
+    pub fn force(self, force: bool) -> Self {
+        Self { force, ..self }
+    }
+
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
+    /// Extracts the package even if it, the root manifest or the lock file have uncommitted
+    /// changes.
+    pub fn allow_dirty(self, allow_dirty: bool) -> Self {
+        Self {
+            allow_dirty,
+            ..self
+        }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Extract<W2> {
+        Extract {
+            stderr,
+            workspace_root: self.workspace_root,
+            src: self.src,
+            dst: self.dst,
+            reverse_dependencies: self.reverse_dependencies,
+            force: self.force,
+            dry_run: self.dry_run,
+            backup: self.backup,
+            allow_dirty: self.allow_dirty,
+            offline: self.offline,
+        }
+    }
+
+    pub fn exec(self) -> crate::Result<()> {
+        let Self {
+            mut stderr,
+            workspace_root,
+            src,
+            dst,
+            reverse_dependencies,
+            force,
+            dry_run,
+            backup,
+            allow_dirty,
+            offline,
+        } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (workspace_root, src, dst) = (workspace_root?, src?, dst?);
+            crate::fs::journal(&workspace_root, "extract");
+
+            let dst = crate::manifest::normalize(&if dst.exists() {
+                dst.join(src.file_name().expect("should be absolute"))
+            } else {
+                dst
+            });
+
+            if let Some(dst_workspace_root) = find_workspace_root(&dst)? {
+                bail!(
+                    "`{}` is under the workspace at `{}`. extract the package to a path outside \
+                     any workspace",
+                    dst.display(),
+                    dst_workspace_root.display(),
+                );
+            }
+
+            if !force {
+                let reverse_dependencies = match reverse_dependencies {
+                    Some(reverse_dependencies) => reverse_dependencies,
+                    None => {
+                        let metadata = cargo_metadata(
+                            Some(&workspace_root.join("Cargo.toml")),
+                            dry_run,
+                            dry_run,
+                            offline,
+                            &workspace_root,
+                        )?;
+                        ReverseDependency::find(&metadata, slice::from_ref(&src))
+                    }
+                };
+                ReverseDependency::ensure_none(
+                    &reverse_dependencies,
+                    slice::from_ref(&src),
+                    "extract",
+                )?;
+            }
+            if !allow_dirty {
+                let mut affected = vec![src.clone()];
+                affected.extend_from_slice(&workspace_files(&workspace_root));
+                crate::git::ensure_clean(&affected, "extract")?;
+            }
+
+            Cp::new(&src, &dst)
+                .dry_run(dry_run)
+                .no_rename(true)
+                .inherit_strategy(InheritStrategy::Inline)
+                .stderr(&mut stderr)
+                .exec()?;
+
+            let manifest_path = dst.join("Cargo.toml");
+            let orig = crate::fs::read_to_string(&manifest_path)?;
+            let mut cargo_toml = orig.parse::<toml_edit::DocumentMut>()?;
+            let workspace = crate::fs::read_toml_edit(workspace_root.join("Cargo.toml"))?;
+
+            crate::manifest::for_each_dependency(&mut cargo_toml, |_, deps, key| {
+                let dep = &mut deps[key];
+                let path = match dep.at("path").as_str() {
+                    Some(path) => crate::manifest::normalize(&dst.join(path)),
+                    None => return Ok(()),
+                };
+                if !path.starts_with(&workspace_root) {
+                    return Ok(());
+                }
+
+                if dep.at("version").is_none() {
+                    let package = crate::fs::read_toml_edit(path.join("Cargo.toml"))?;
+                    let version = match package.at("package").at("version").as_str() {
+                        Some(version) => version,
+                        None if package
+                            .at("package")
+                            .at("version")
+                            .at("workspace")
+                            .as_bool()
+                            == Some(true) =>
+                        {
+                            workspace
+                                .at("workspace")
+                                .at("package")
+                                .at("version")
+                                .as_str()
+                                .with_context(|| {
+                                    "`workspace.package.version` is not defined in the workspace"
+                                })?
+                        }
+                        None => bail!(
+                            "`{}` does not have `package.version`",
+                            path.join("Cargo.toml").display(),
+                        ),
+                    };
+                    crate::manifest::insert_str(dep, "version", version);
+                }
+
+                if let Some(table) = dep.as_inline_table_mut() {
+                    table.remove("path");
+                    table.fmt();
+                } else if let Some(table) = dep.as_table_mut() {
+                    table.remove("path");
+                }
+                let version = dep.at("version").as_str().map(ToOwned::to_owned);
+                if let (Some(version), Some(1)) = (&version, dep.as_inline_table().map(|t| t.len()))
+                {
+                    crate::manifest::replace_str(dep, version);
+                }
+
+                stderr.status(
+                    "Converting",
+                    format!(
+                        "`{}` to a version dependency ({:?})",
+                        key,
+                        version.unwrap_or_default(),
+                    ),
+                )?;
+                Ok(())
+            })?;
+
+            if cargo_toml.at("workspace").is_none() {
+                stderr.status(
+                    "Adding",
+                    format!("`[workspace]` to `{}`", manifest_path.display()),
+                )?;
+                cargo_toml["workspace"] = toml_edit::table();
+            }
+
+            let cargo_toml = cargo_toml.to_string();
+            if dry_run {
+                crate::fs::dry_run_diff(&manifest_path, &manifest_path, &orig);
+            }
+            crate::fs::write(&manifest_path, cargo_toml, dry_run)?;
+
+            Rm {
+                workspace_root: Ok(workspace_root),
+                paths: Ok(vec![src]),
+                reverse_dependencies: Some(vec![]),
+                force: true,
+                dry_run,
+                backup,
+                allow_dirty: true,
+                trash: false,
+                offline,
+                stderr: &mut stderr,
+            }
+            .exec()?;
+            stderr.dry_run_diffs()?;
+            Ok(())
+        })
+        .map_err(Into::into)
+    }
+}
+
+#[derive(Debug)]
+pub struct Adopt<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    src: anyhow::Result<PathBuf>,
+    dst: anyhow::Result<PathBuf>,
+    copy: bool,
+    inherit: bool,
+    dry_run: bool,
+    backup: bool,
+    offline: bool,
+    allow_dirty: bool,
+    stderr: W,
+}
+
+impl Adopt<NoColor<Sink>> {
+    pub fn new(workspace_root: &Path, src: &Path, dst: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+            src: ensure_absolute(src),
+            dst: ensure_absolute(dst),
+            copy: false,
+            inherit: false,
+            dry_run: false,
+            backup: false,
+            offline: false,
+            allow_dirty: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> Adopt<W> {
+    /// Keeps the original directory.
+    pub fn copy(self, copy: bool) -> Self {
+        Self { copy, ..self }
+    }
+
+    /// Replaces fields and dependencies equal to the ones in `[workspace.package]` and
+    /// `[workspace.dependencies]` with `{ workspace = true }`.
+    pub fn inherit(self, inherit: bool) -> Self {
+        Self { inherit, ..self }
+    }
+
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    /// Adopts the package even if it, the root manifest or the lock file have uncommitted
+    /// changes.
+    pub fn allow_dirty(self, allow_dirty: bool) -> Self {
+        Self {
+            allow_dirty,
+            ..self
+        }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Adopt<W2> {
+        Adopt {
+            stderr,
+            workspace_root: self.workspace_root,
+            src: self.src,
+            dst: self.dst,
+            copy: self.copy,
+            inherit: self.inherit,
+            dry_run: self.dry_run,
+            backup: self.backup,
+            allow_dirty: self.allow_dirty,
+            offline: self.offline,
+        }
+    }
+
+    pub fn exec(self) -> crate::Result<()> {
+        let Self {
+            mut stderr,
+            workspace_root,
+            src,
+            dst,
+            copy,
+            inherit,
+            dry_run,
+            backup,
+            allow_dirty,
+            offline,
+        } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (workspace_root, src, dst) = (workspace_root?, src?, dst?);
+            crate::fs::journal(&workspace_root, "adopt");
+            let src = crate::manifest::normalize(&src);
+
+            let dst = crate::manifest::normalize(&if dst.exists() {
+                dst.join(src.file_name().expect("should be absolute"))
+            } else {
+                dst
+            });
+
+            if find_workspace_root(&dst)?.as_ref() != Some(&workspace_root) {
+                bail!(
+                    "`{}` is not under the workspace at `{}`",
+                    dst.display(),
+                    workspace_root.display(),
+                );
+            }
+            if !src.join("Cargo.toml").exists() {
+                return Err(Error::NotAPackage {
+                    path: src,
+                    action: "adopt",
+                }
+                .into());
+            }
+            ensure!(
+                !workspace_root.starts_with(&src),
+                "`{}` contains the workspace",
+                src.display(),
+            );
+            if !allow_dirty {
+                let mut affected = workspace_files(&workspace_root).to_vec();
+                if !copy {
+                    affected.push(src.clone());
+                }
+                crate::git::ensure_clean(&affected, "adopt")?;
+            }
+
+            Cp::new(&src, &dst)
+                .dry_run(dry_run)
+                .no_rename(true)
+                .stderr(&mut stderr)
+                .exec()?;
+
+            let manifest_path = dst.join("Cargo.toml");
+            let orig = crate::fs::read_to_string(&manifest_path)?;
+            let mut cargo_toml = orig.parse::<toml_edit::DocumentMut>()?;
+
+            if cargo_toml.as_table_mut().remove("workspace").is_some() {
+                stderr.status(
+                    "Removing",
+                    format!("`[workspace]` from `{}`", manifest_path.display()),
+                )?;
+            }
+
+            if inherit {
+                let workspace = crate::fs::read_toml_edit(workspace_root.join("Cargo.toml"))?;
+                for field in crate::manifest::inherit_from_workspace(
+                    &mut cargo_toml,
+                    &workspace,
+                    &workspace_root,
+                    &dst,
+                )? {
+                    stderr.status("Inheriting", format!("`{}`", field))?;
+                }
+            }
+
+            let name = cargo_toml
+                .at("package")
+                .at("name")
+                .as_str()
+                .map(ToOwned::to_owned);
+
+            let cargo_toml = cargo_toml.to_string();
+            if dry_run {
+                crate::fs::dry_run_diff(&manifest_path, &manifest_path, &orig);
+            }
+            crate::fs::write(&manifest_path, cargo_toml, dry_run)?;
+
+            if let Some(name) = &name {
+                let lock_path = workspace_root.join("Cargo.lock");
+                let migrated =
+                    crate::lockfile::migrate(&src.join("Cargo.lock"), &lock_path, name, dry_run)?;
+                if migrated > 0 {
+                    stderr.status(
+                        "Migrating",
+                        format!(
+                            "{} locked package(s) to `{}`",
+                            migrated,
+                            lock_path.display(),
+                        ),
+                    )?;
+                }
+            }
+            if dst.join("Cargo.lock").exists() {
+                stderr.status_with_color(
+                    "Removing",
+                    format!("`{}`", dst.join("Cargo.lock").display()),
+                    termcolor::Color::Red,
+                )?;
+                crate::fs::remove_file(dst.join("Cargo.lock"), dry_run)?;
+            }
+
+            if !copy {
+                stderr.status_with_color(
+                    "Removing",
+                    format!("directory `{}`", src.display()),
+                    termcolor::Color::Red,
+                )?;
+                crate::fs::remove_dir_all(&src, dry_run)?;
+            }
+
+            stderr.dry_run_diffs()?;
+
+            if dry_run {
+                stderr.warn("not modifying the manifest due to dry run")?;
+            } else {
+                update_lockfile(&workspace_root, offline, &mut stderr)?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
+    }
+}
+
+#[derive(Debug)]
+pub struct Mv<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    src: anyhow::Result<PathBuf>,
+    dst: anyhow::Result<PathBuf>,
+    dry_run: bool,
+    backup: bool,
+    no_rename: bool,
+    rename_strategy: RenameStrategy,
+    inherit_strategy: InheritStrategy,
+    exclude: Vec<String>,
+    include_vcs: bool,
+    allow_dirty: bool,
+    untracked: UntrackedStrategy,
+    trash: bool,
+    offline: bool,
+    stderr: W,
+}
+
+impl Mv<NoColor<Sink>> {
+    pub fn new(workspace_root: &Path, src: &Path, dst: &Path) -> Self {
+        Self {
+            workspace_root: ensure_absolute(workspace_root),
+            src: ensure_absolute(src),
+            dst: ensure_absolute(dst),
+            dry_run: false,
+            backup: false,
+            no_rename: false,
+            rename_strategy: RenameStrategy::default(),
+            inherit_strategy: InheritStrategy::default(),
+            exclude: vec![],
+            include_vcs: false,
+            allow_dirty: false,
+            untracked: UntrackedStrategy::default(),
+            trash: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+
+    pub fn from_metadata(metadata: &Metadata, src: &str, dst: &Path) -> Self {
+        Self {
+            workspace_root: Ok(metadata.workspace_root.clone()),
+            src: metadata.query_for_member(Some(src)).map(|member| {
+                member
+                    .manifest_path
+                    .parent()
+                    .expect(r#"`manifest_path` should end with "Cargo.toml""#)
+                    .to_owned()
+            }),
+            dst: ensure_absolute(dst),
+            dry_run: false,
+            backup: false,
+            no_rename: false,
+            rename_strategy: RenameStrategy::default(),
+            inherit_strategy: InheritStrategy::default(),
+            exclude: vec![],
+            include_vcs: false,
+            allow_dirty: false,
+            untracked: UntrackedStrategy::default(),
+            trash: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> Mv<W> {
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
+    pub fn no_rename(self, no_rename: bool) -> Self {
+        Self { no_rename, ..self }
+    }
+
+    pub fn rename_strategy(self, rename_strategy: RenameStrategy) -> Self {
+        Self {
+            rename_strategy,
+            ..self
+        }
+    }
+
+    pub fn inherit_strategy(self, inherit_strategy: InheritStrategy) -> Self {
+        Self {
+            inherit_strategy,
+            ..self
+        }
+    }
+
+    /// Skips files matching the gitignore-style globs, relative to the package root.
+    pub fn exclude<Ss: IntoIterator<Item = S>, S: AsRef<str>>(self, exclude: Ss) -> Self {
+        let exclude = exclude.into_iter().map(|s| s.as_ref().to_owned()).collect();
+        Self { exclude, ..self }
+    }
+
+    /// Moves VCS directories such as `.git` too.
+    pub fn include_vcs(self, include_vcs: bool) -> Self {
+        Self {
+            include_vcs,
+            ..self
+        }
+    }
+
+    /// Moves the package even if it, the root manifests, the lock files or the dependents'
+    /// manifests have uncommitted changes.
+    ///
+    /// In a Git work tree, the moved files are staged with `git add -f`, so their unstaged changes
+    /// are staged as well.
+    pub fn allow_dirty(self, allow_dirty: bool) -> Self {
+        Self {
+            allow_dirty,
+            ..self
+        }
+    }
+
+    pub fn untracked(self, untracked: UntrackedStrategy) -> Self {
+        Self { untracked, ..self }
+    }
+
+    /// Moves the source directory to the trash instead of deleting it once it is copied. See
+    /// `Rm::trash`.
+    pub fn trash(self, trash: bool) -> Self {
+        Self { trash, ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Mv<W2> {
+        Mv {
+            stderr,
+            workspace_root: self.workspace_root,
+            src: self.src,
+            dst: self.dst,
+            dry_run: self.dry_run,
+            backup: self.backup,
+            no_rename: self.no_rename,
+            rename_strategy: self.rename_strategy,
+            inherit_strategy: self.inherit_strategy,
+            exclude: self.exclude,
+            include_vcs: self.include_vcs,
+            allow_dirty: self.allow_dirty,
+            untracked: self.untracked,
+            trash: self.trash,
+            offline: self.offline,
+        }
+    }
+
+    pub fn exec(self) -> crate::Result<()> {
+        let Self {
+            mut stderr,
+            workspace_root,
+            src,
+            dst,
+            dry_run,
+            backup,
+            no_rename,
+            rename_strategy,
+            inherit_strategy,
+            exclude,
+            include_vcs,
+            allow_dirty,
+            untracked,
+            trash,
+            offline,
+        } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let (workspace_root, src, dst) = (workspace_root?, src?, dst?);
+            crate::fs::journal(&workspace_root, "mv");
+
+            let dst = crate::manifest::normalize(&if dst.exists() {
+                dst.join(src.file_name().expect("should be absolute"))
+            } else {
+                dst
+            });
+
+            let dst_workspace_root =
+                find_workspace_root(&dst)?.filter(|root| *root != workspace_root);
+
+            let mut dependents = find_packages(&workspace_root, &mut stderr)?;
+            if let Some(dst_workspace_root) = &dst_workspace_root {
+                dependents.extend(find_packages(dst_workspace_root, &mut stderr)?);
+                dependents.sort();
+                dependents.dedup();
+            }
+
+            if !allow_dirty {
+                let mut affected = vec![src.clone(), dst.clone()];
+                affected.extend_from_slice(&workspace_files(&workspace_root));
+                if let Some(dst_workspace_root) = &dst_workspace_root {
+                    affected.extend_from_slice(&workspace_files(dst_workspace_root));
+                }
+                for dependent in dependents.iter().filter(|&p| *p != src) {
+                    if depends_on_path(dependent, &src)? {
+                        affected.push(dependent.join("Cargo.toml"));
+                    }
+                }
+                crate::git::ensure_clean(&affected, "move")?;
+            }
+
+            // In a Git work tree, the tracked files are moved and the move is staged in the index.
+            let repo = crate::git::Repository::discover(&src)?;
+            let tracked = repo
+                .as_ref()
+                .map(|repo| repo.tracked_files(&src))
+                .transpose()?;
+
+            if let Some(tracked) = &tracked {
+                let target_dir = crate::fs::target_dir(&workspace_root);
+                let overrides = copy_overrides(&src, &target_dir, &exclude, include_vcs)?;
+                let skipped = tracked
+                    .iter()
+                    .filter(|file| {
+                        file.ancestors()
+                            .filter(|p| !p.as_os_str().is_empty())
+                            .any(|p| overrides.matched(src.join(p), p != *file).is_ignore())
+                    })
+                    .collect::<Vec<_>>();
+                if !skipped.is_empty() {
+                    stderr.warn(format!(
+                        "{} tracked file(s) in `{}` are not moved. they will be removed from the \
+                         index and deleted: {}",
+                        skipped.len(),
+                        src.display(),
+                        skipped
+                            .iter()
+                            .format_with(", ", |p, f| f(&format_args!("`{}`", p.display()))),
+                    ))?;
+                }
+            }
+
+            let cp = Cp::new(&src, &dst)
+                .dry_run(dry_run)
+                .no_rename(no_rename)
+                .inherit_strategy(inherit_strategy)
+                .exclude(exclude)
+                .include_vcs(include_vcs);
+            // Ignored files are moved too, since whatever is not copied is removed with `src`.
+            let cp = match (&tracked, untracked) {
+                (Some(tracked), UntrackedStrategy::Remove) => cp.files(tracked.clone()),
+                _ => cp.no_ignore(true),
+            };
+            cp.stderr(&mut stderr).exec()?;
+
+            let old_name = crate::fs::read_toml_edit(src.join("Cargo.toml"))?
+                .at("package")
+                .at("name")
+                .as_str()
+                .map(ToOwned::to_owned);
+            let new_name = dst
+                .file_name()
+                .expect("should be absolute")
+                .to_str()
+                .with_context(|| format!("{:?} is not valid UTF-8", dst.file_name()))?;
+            let rename = match &old_name {
+                Some(old_name) if !no_rename && old_name != new_name => {
+                    Some((&**old_name, new_name, rename_strategy))
+                }
+                _ => None,
+            };
+
+            if let (Some(dst_workspace_root), Some(old_name)) = (&dst_workspace_root, &old_name) {
+                let lock_path = dst_workspace_root.join("Cargo.lock");
+                let migrated = crate::lockfile::migrate(
+                    &workspace_root.join("Cargo.lock"),
+                    &lock_path,
+                    old_name,
+                    dry_run,
+                )?;
+                if migrated > 0 {
+                    stderr.status(
+                        "Migrating",
+                        format!(
+                            "{} locked package(s) to `{}`",
+                            migrated,
+                            lock_path.display(),
+                        ),
+                    )?;
+                }
+            }
+
+            for dependent in dependents.iter().filter(|&p| *p != src) {
+                update_dependent(dependent, &src, &dst, rename, dry_run, &mut stderr)?;
+            }
+
+            Rm::new(&workspace_root, [&src])
+                .reverse_dependencies(vec![])
+                .dry_run(dry_run)
+                .allow_dirty(true)
+                .trash(trash)
+                .offline(offline)
+                .stderr(&mut stderr)
+                .exec()?;
+
+            if let (Some(repo), Some(tracked)) = (&repo, &tracked) {
+                if repo.contains(&dst) {
+                    stderr.status(
+                        "Staging",
+                        format!("`{}` -> `{}`", src.display(), dst.display()),
+                    )?;
+                    if !dry_run {
+                        let moved = tracked
+                            .iter()
+                            .filter(|file| dst.join(file).symlink_metadata().is_ok())
+                            .cloned()
+                            .collect();
+                        repo.stage_move(&src, &dst, &moved)?;
+                    }
+                }
+            }
+
+            stderr.dry_run_diffs()?;
+
+            if let Some(dst_workspace_root) = dst_workspace_root {
+                if dry_run {
+                    stderr.warn("not updating the lock file due to dry run")?;
+                } else {
+                    update_lockfile(&dst_workspace_root, offline, &mut stderr)?;
+                }
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
+    }
+}
+
+/// A step of a `Plan`. Paths are relative to the workspace root.
+#[derive(Deserialize, Serialize, IntoStaticStr, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "op", rename_all = "kebab-case", deny_unknown_fields)]
+#[strum(serialize_all = "kebab-case")]
+pub enum PlanStep {
+    Include {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        force: bool,
+    },
+    Exclude {
+        paths: Vec<PathBuf>,
+    },
+    Deactivate {
+        paths: Vec<PathBuf>,
+    },
+    New {
+        path: PathBuf,
+        #[serde(default)]
+        lib: bool,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Cp {
+        src: PathBuf,
+        dst: PathBuf,
+        #[serde(default, rename = "no-rename")]
+        no_rename: bool,
+    },
+    Mv {
+        src: PathBuf,
+        dst: PathBuf,
+        #[serde(default, rename = "no-rename")]
+        no_rename: bool,
+    },
+    Rm {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        force: bool,
+    },
+}
+
+/// The contents of a plan file.
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PlanFile {
+    #[serde(default, rename = "step")]
+    pub steps: Vec<PlanStep>,
+}
+
+/// Runs `PlanStep`s in one transaction, updating `Cargo.lock` once at the end.
+#[derive(Debug)]
+pub struct Plan<W> {
+    workspace_root: anyhow::Result<PathBuf>,
+    steps: Vec<PlanStep>,
+    reverse_dependencies: Vec<ReverseDependency>,
+    dry_run: bool,
+    backup: bool,
+    allow_dirty: bool,
+    trash: bool,
+    offline: bool,
+    stderr: W,
+}
+
+impl Plan<NoColor<Sink>> {
+    pub fn from_metadata<Ss: IntoIterator<Item = PlanStep>>(
+        metadata: &Metadata,
+        steps: Ss,
+    ) -> Self {
+        // Which packages end up removed is known only after following the `mv` steps in `exec`,
+        // so every dependency between the members is kept.
+        let reverse_dependencies = member_dirs(metadata)
+            .iter()
+            .flat_map(|dir| ReverseDependency::find(metadata, slice::from_ref(dir)))
+            .collect();
+
+        Self {
+            workspace_root: Ok(metadata.workspace_root.clone()),
+            reverse_dependencies,
+            steps: steps.into_iter().collect(),
+            dry_run: false,
+            backup: false,
+            allow_dirty: false,
+            trash: false,
+            offline: false,
+            stderr: NoColor::new(io::sink()),
+        }
+    }
+}
+
+impl<W: WriteColor> Plan<W> {
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    /// Keeps the original contents of overwritten files as `<file name>.bak`.
+    pub fn backup(self, backup: bool) -> Self {
+        Self { backup, ..self }
+    }
+
+    /// Applies the plan even if the affected files have uncommitted changes.
+    pub fn allow_dirty(self, allow_dirty: bool) -> Self {
+        Self {
+            allow_dirty,
+            ..self
+        }
+    }
+
+    /// Moves the directories removed by `rm` and `mv` steps to the trash. See `Rm::trash`.
+    pub fn trash(self, trash: bool) -> Self {
+        Self { trash, ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { offline, ..self }
+    }
+
+    pub fn stderr<W2: WriteColor>(self, stderr: W2) -> Plan<W2> {
+        Plan {
+            workspace_root: self.workspace_root,
+            steps: self.steps,
+            reverse_dependencies: self.reverse_dependencies,
+            dry_run: self.dry_run,
+            backup: self.backup,
+            allow_dirty: self.allow_dirty,
+            trash: self.trash,
+            offline: self.offline,
+            stderr,
+        }
+    }
+
+    /// Validates every step against the current state of the workspace, then runs them in order.
+    ///
+    /// If a step fails, the steps before it are rolled back too.
+    pub fn exec(self) -> crate::Result<()> {
+        let Self {
+            workspace_root,
+            steps,
+            reverse_dependencies,
+            dry_run,
+            backup,
+            allow_dirty,
+            trash,
+            offline,
+            mut stderr,
+        } = self;
+        crate::fs::transaction(&mut stderr, |mut stderr| {
+            crate::fs::backup(backup);
+
+            let workspace_root = workspace_root?;
+            crate::fs::journal(&workspace_root, "apply");
+
+            let resolve = |path: &Path| crate::manifest::normalize(&workspace_root.join(path));
+
+            // Whether each path is a package once the previous steps are done.
+            let mut planned = HashMap::<PathBuf, bool>::new();
+            let mut affected = workspace_files(&workspace_root).to_vec();
+            // The original paths of the packages moved by the previous steps.
+            let mut origins = HashMap::<PathBuf, PathBuf>::new();
+            let mut removed = vec![];
+            // The original paths of all the packages removed, including with `force`.
+            let mut gone = vec![];
+            for (i, step) in steps.iter().enumerate() {
+                let is_package = |planned: &HashMap<PathBuf, bool>, path: &Path| {
+                    planned
+                        .get(path)
+                        .copied()
+                        .unwrap_or_else(|| path.join("Cargo.toml").exists())
+                };
+                let ensure_package = |planned: &HashMap<PathBuf, bool>, path: &Path| {
+                    ensure!(
+                        is_package(planned, path),
+                        "step {} (`{}`): `{}` is not a package at that point",
+                        i + 1,
+                        <&str>::from(step),
+                        path.display(),
+                    );
+                    Ok(())
+                };
+                let ensure_vacant = |planned: &HashMap<PathBuf, bool>, path: &Path| {
+                    ensure!(
+                        !planned.get(path).copied().unwrap_or_else(|| path.exists()),
+                        "step {} (`{}`): `{}` exists at that point",
+                        i + 1,
+                        <&str>::from(step),
+                        path.display(),
+                    );
+                    Ok(())
+                };
+
+                match step {
+                    PlanStep::Include { paths, force } => {
+                        for path in paths.iter().map(|p| resolve(p)) {
+                            if !force {
+                                ensure_package(&planned, &path)?;
+                            }
+                        }
+                    }
+                    PlanStep::Exclude { .. } | PlanStep::Deactivate { .. } => {}
+                    PlanStep::New { path, .. } => {
+                        let path = resolve(path);
+                        ensure_vacant(&planned, &path)?;
+                        planned.insert(path.clone(), true);
+                        affected.push(path);
+                    }
+                    PlanStep::Cp { src, dst, .. } | PlanStep::Mv { src, dst, .. } => {
+                        let (src, dst) = (resolve(src), resolve(dst));
+                        ensure_package(&planned, &src)?;
+                        let is_dir = planned.get(&dst).copied().unwrap_or_else(|| dst.is_dir());
+                        let dst = if is_dir {
+                            dst.join(src.file_name().expect("should be absolute"))
+                        } else {
+                            dst
+                        };
+                        ensure_vacant(&planned, &dst)?;
+                        if let PlanStep::Mv { .. } = step {
+                            let origin = origins.remove(&src).unwrap_or_else(|| src.clone());
+                            origins.insert(dst.clone(), origin);
+                            planned.insert(src.clone(), false);
+                            affected.push(src);
+                        }
+                        planned.insert(dst.clone(), true);
+                        affected.push(dst);
+                    }
+                    PlanStep::Rm { paths, force } => {
+                        for path in paths.iter().map(|p| resolve(p)) {
+                            let origin = origins.get(&path).unwrap_or(&path).clone();
+                            if !force {
+                                ensure_package(&planned, &path)?;
+                                removed.push(origin.clone());
+                            }
+                            gone.push(origin);
+                            planned.insert(path.clone(), false);
+                            affected.push(path);
+                        }
+                    }
+                }
+            }
+
+            // Moved dependents are still there, at their new paths.
+            let reverse_dependencies = reverse_dependencies
+                .into_iter()
+                .filter(|d| !gone.contains(&d.dependent_dir))
+                .collect::<Vec<_>>();
+            ReverseDependency::ensure_none(&reverse_dependencies, &removed, "remove")?;
+
+            if !allow_dirty {
+                crate::git::ensure_clean(&affected, "apply the plan")?;
+            }
+
+            crate::fs::defer_lock_updates();
+
+            for (i, step) in steps.into_iter().enumerate() {
+                let op = <&str>::from(&step);
+                stderr.status("Applying", format!("step {} (`{}`)", i + 1, op))?;
+
+                // In dry run, packages created by the previous steps do not exist.
+                let missing = |path: &Path| dry_run && !path.exists();
+
+                match step {
+                    PlanStep::Include { paths, force } => {
+                        let paths = paths.iter().map(|p| resolve(p)).collect::<Vec<_>>();
+                        Include::new(&workspace_root, &paths)
+                            .force(force || paths.iter().any(|p| missing(p)))
+                            .dry_run(dry_run)
+                            .offline(offline)
+                            .stderr(&mut stderr)
+                            .exec()
+                    }
+                    PlanStep::Exclude { paths } => {
+                        Exclude::new(&workspace_root, paths.iter().map(|p| resolve(p)))
+                            .dry_run(dry_run)
+                            .stderr(&mut stderr)
+                            .exec()
+                    }
+                    PlanStep::Deactivate { paths } => {
+                        Deactivate::new(&workspace_root, paths.iter().map(|p| resolve(p)))
+                            .dry_run(dry_run)
+                            .stderr(&mut stderr)
+                            .exec()
+                    }
+                    PlanStep::New { path, lib, name } => New::new(&workspace_root, &resolve(&path))
+                        .cargo_new_lib(lib)
+                        .cargo_new_name(name)
+                        .offline(offline)
+                        .dry_run(dry_run)
+                        .stderr(&mut stderr)
+                        .exec(),
+                    PlanStep::Cp { src, .. } | PlanStep::Mv { src, .. }
+                        if missing(&resolve(&src)) =>
+                    {
+                        stderr.warn(format!(
+                            "skipping as `{}` is created by a previous step",
+                            resolve(&src).display(),
+                        ))?;
+                        Ok(())
+                    }
+                    PlanStep::Cp {
+                        src,
+                        dst,
+                        no_rename,
+                    } => Cp::new(&resolve(&src), &resolve(&dst))
+                        .no_rename(no_rename)
+                        .dry_run(dry_run)
+                        .stderr(&mut stderr)
+                        .exec(),
+                    PlanStep::Mv {
+                        src,
+                        dst,
+                        no_rename,
+                    } => Mv::new(&workspace_root, &resolve(&src), &resolve(&dst))
+                        .no_rename(no_rename)
+                        .allow_dirty(true)
+                        .trash(trash)
+                        .offline(offline)
+                        .dry_run(dry_run)
+                        .stderr(&mut stderr)
+                        .exec(),
+                    PlanStep::Rm { paths, force } => {
+                        let paths = paths.iter().map(|p| resolve(p)).collect::<Vec<_>>();
+                        Rm::new(&workspace_root, &paths)
+                            .reverse_dependencies(vec![])
+                            .force(force || paths.iter().any(|p| missing(p)))
+                            .allow_dirty(true)
+                            .trash(trash)
+                            .offline(offline)
+                            .dry_run(dry_run)
+                            .stderr(&mut stderr)
+                            .exec()
+                    }
+                }
+                .with_context(|| format!("failed to apply step {} (`{}`)", i + 1, op))?;
+            }
+
+            stderr.dry_run_diffs()?;
+
+            let workspace_roots = crate::fs::take_deferred_lock_updates();
+            if dry_run {
+                stderr.warn("not updating the lock file due to dry run")?;
+            }
+            for workspace_root in workspace_roots {
+                update_lockfile(&workspace_root, offline, &mut stderr)?;
+            }
+            Ok(())
+        })
+        .map_err(Into::into)
+    }
+}
+
+/// How to take a package out of `workspace.members` when it is matched by a glob pattern.
+#[derive(
+    EnumString, EnumVariantNames, IntoStaticStr, Default, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum GlobStrategy {
+    /// Keep the glob and add the package to `workspace.exclude`.
+    #[default]
+    Exclude,
+    /// Replace the glob with the packages it currently matches.
+    Expand,
+}
+
+/// How to update dependents when `Mv` changes `package.name`.
+#[derive(
+    EnumString, EnumVariantNames, IntoStaticStr, Default, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RenameStrategy {
+    /// Keep the dependency key as an alias and add `package = "<new>"`.
+    #[default]
+    Alias,
+    /// Rename the dependency key. `use` sites have to be updated by hand.
+    Key,
+}
+
+/// What `Cp` does with fields inherited from `[workspace.*]` when the package leaves the workspace.
+#[derive(
+    EnumString, EnumVariantNames, IntoStaticStr, Default, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum InheritStrategy {
+    /// Add the missing entries to the destination workspace.
+    #[default]
+    Add,
+    /// Replace `{ workspace = true }` with the values. Always used when there is no destination
+    /// workspace.
+    Inline,
+}
+
+/// What `Mv` does with untracked and ignored files when the package is in a Git work tree.
+#[derive(
+    EnumString, EnumVariantNames, IntoStaticStr, Default, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum UntrackedStrategy {
+    /// Move them with the tracked files, except the target directory and the ones matched by
+    /// `--exclude`.
+    #[default]
+    Move,
+    /// Move only the tracked files and remove the rest with the source directory.
+    Remove,
+}
+
+fn ensure_absolute(path: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
+    let path = path.as_ref();
+    ensure!(path.is_absolute(), "must be absolute: {}", path.display());
+    Ok(path.to_owned())
+}
+
+/// Finds directories that contain a `Cargo.toml`, respecting `.gitignore`s. The target directory,
+/// which holds the trash, is skipped even outside a Git work tree.
+fn find_packages(root: &Path, mut stderr: impl WriteColor) -> anyhow::Result<Vec<PathBuf>> {
+    let target = crate::fs::target_dir(root);
+    let mut packages = vec![];
+    for entry in WalkBuilder::new(root)
+        .filter_entry(move |entry| entry.path() != target)
+        .build()
+    {
+        match entry {
+            Ok(entry) => {
+                if entry.path().ends_with("Cargo.toml") {
+                    let dir = entry.path().parent().expect("should not empty");
+                    packages.push(dir.to_owned());
+                }
+            }
+            Err(err) => stderr.warn(err)?,
+        }
+    }
+    Ok(packages)
+}
+
+/// Fails if the package at `path` is the root of a nested workspace or belongs to another
+/// workspace. If `absorb` is `true`, removes `[workspace]` and `package.workspace` instead and
+/// returns the members of the nested workspace.
+fn absorb_workspace(
+    workspace_root: &Path,
+    path: &Path,
+    absorb: bool,
+    dry_run: bool,
+    mut stderr: impl WriteColor,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let manifest_path = path.join("Cargo.toml");
+    let orig = crate::fs::read_to_string(&manifest_path)?;
+    let mut cargo_toml = orig.parse::<toml_edit::DocumentMut>()?;
+
+    let nested = cargo_toml.at("workspace").is_table();
+    let foreign = cargo_toml
+        .at("package")
+        .at("workspace")
+        .as_str()
+        .map(|workspace| crate::manifest::normalize(&path.join(workspace)))
+        .filter(|workspace| workspace != workspace_root);
+
+    if !absorb {
+        if nested {
+            return Err(Error::NestedWorkspace {
+                path: path.to_owned(),
+            }
+            .into());
+        }
+        if let Some(workspace) = foreign {
+            return Err(Error::ForeignWorkspace {
+                path: path.to_owned(),
+                workspace,
+            }
+            .into());
+        }
+        return Ok(vec![]);
+    }
+
+    let mut members = vec![];
+
+    if nested {
+        ensure!(
+            cargo_toml.at("package").is_table(),
+            "`{}` is a virtual manifest. include its members instead",
+            manifest_path.display(),
+        );
+        let excluded = cargo_toml
+            .at("workspace")
+            .at("exclude")
+            .as_array()
+            .into_iter()
+            .flat_map(|exclude| exclude.iter())
+            .flat_map(|exclude| exclude.as_str())
+            .map(|exclude| crate::manifest::normalize(&path.join(exclude)))
+            .collect::<Vec<_>>();
+        for member in cargo_toml
+            .at("workspace")
+            .at("members")
+            .as_array()
+            .into_iter()
+            .flat_map(|members| members.iter())
+            .flat_map(|member| member.as_str())
+        {
+            let pattern = crate::manifest::normalize(&glob_path(path, member));
+            let pattern = pattern
+                .to_str()
+                .with_context(|| format!("{:?} is not valid UTF-8", pattern))?;
+            for member in glob::glob_with(pattern, GLOB_MATCH_OPTIONS)? {
+                let member = member?;
+                if member.join("Cargo.toml").exists()
+                    && member != path
+                    && !excluded.iter().any(|e| member.starts_with(e))
+                {
+                    members.push(member);
+                }
+            }
+        }
+        cargo_toml.as_table_mut().remove("workspace");
+        stderr.status(
+            "Absorbing",
+            format!("`[workspace]` in `{}`", manifest_path.display()),
+        )?;
+    }
+
+    if foreign.is_some() {
+        if let Some(package) = cargo_toml["package"].as_table_mut() {
+            package.remove("workspace");
+        }
+        stderr.status(
+            "Removing",
+            format!("`package.workspace` from `{}`", manifest_path.display()),
+        )?;
+    }
+
+    if !(nested || foreign.is_some()) {
+        return Ok(vec![]);
+    }
+
+    let cargo_toml = cargo_toml.to_string();
+    if dry_run {
+        crate::fs::dry_run_diff(&manifest_path, &manifest_path, &orig);
+    }
+    crate::fs::write(&manifest_path, cargo_toml, dry_run)?;
+
+    let nested_lock_path = path.join("Cargo.lock");
+    if nested && nested_lock_path.exists() {
+        let lock_path = workspace_root.join("Cargo.lock");
+        let mut migrated = 0;
+        for package in iter::once(path).chain(members.iter().map(|m| &**m)) {
+            let name = crate::fs::read_toml_edit(package.join("Cargo.toml"))?
+                .at("package")
+                .at("name")
+                .as_str()
+                .map(ToOwned::to_owned);
+            if let Some(name) = name {
+                migrated +=
+                    crate::lockfile::migrate(&nested_lock_path, &lock_path, &name, dry_run)?;
+            }
+        }
+        if migrated > 0 {
+            stderr.status(
+                "Migrating",
+                format!(
+                    "{} locked package(s) to `{}`",
+                    migrated,
+                    lock_path.display()
+                ),
+            )?;
+        }
+        stderr.status_with_color(
+            "Removing",
+            format!("`{}`", nested_lock_path.display()),
+            termcolor::Color::Red,
+        )?;
+        crate::fs::remove_file(&nested_lock_path, dry_run)?;
+    }
+
+    Ok(members)
+}
+
+/// Returns the target of the symbolic link `link` in the package at `src_root` for the copy at
+/// `dst_root`. Relative targets outside the package are rewritten to keep pointing at the same
+/// file, and absolute targets inside the package to point at the copy.
+fn rebase_symlink(link: &Path, src_root: &Path, dst_root: &Path) -> anyhow::Result<PathBuf> {
+    let target =
+        std::fs::read_link(link).with_context(|| format!("failed to read `{}`", link.display()))?;
+    if target.is_absolute() {
+        return Ok(
+            match crate::manifest::normalize(&target).strip_prefix(src_root) {
+                Ok(relative) => dst_root.join(relative),
+                Err(_) => target,
+            },
+        );
+    }
+    let parent = link.parent().expect("should not be the root");
+    let resolved = crate::manifest::normalize(&parent.join(&target));
+    if resolved.starts_with(src_root) {
+        return Ok(target);
+    }
+    let dst = dst_root.join(link.strip_prefix(src_root)?);
+    let dst_parent = dst.parent().expect("should not be the root");
+    crate::manifest::relative_path(dst_parent, &resolved).map(Into::into)
+}
+
+/// Updates dependencies of the package at `package_root` on a package moved from `from` to
+/// `to`. `rename` is the old name, the new name and the strategy if the package is renamed.
+/// Returns `true` if the manifest in `package_root` has a `path` dependency on `path`.
+fn depends_on_path(package_root: &Path, path: &Path) -> anyhow::Result<bool> {
+    let mut cargo_toml = crate::fs::read_toml_edit(package_root.join("Cargo.toml"))?;
+    let mut found = false;
+    crate::manifest::for_each_dependency(&mut cargo_toml, |_, deps, key| {
+        if let Some(dep_path) = deps.at(key).at("path").as_str() {
+            found |= crate::manifest::normalize(&package_root.join(dep_path)) == path;
+        }
+        Ok(())
+    })?;
+    Ok(found)
+}
+
+fn update_dependent(
+    package_root: &Path,
+    from: &Path,
+    to: &Path,
+    rename: Option<(&str, &str, RenameStrategy)>,
+    dry_run: bool,
+    mut stderr: impl WriteColor,
+) -> anyhow::Result<bool> {
+    let manifest_path = package_root.join("Cargo.toml");
+    let mut cargo_toml = crate::fs::read_toml_edit(&manifest_path)?;
+    let orig = cargo_toml.to_string();
+    let mut modified = false;
+    let mut renamed_keys = vec![];
+
+    crate::manifest::for_each_dependency(&mut cargo_toml, |_, deps, key| {
+        let dep = &mut deps[key];
+        if !dep.is_table_like() {
+            return Ok(());
+        }
+        let path = match dep.at("path").as_str() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if crate::manifest::normalize(&package_root.join(path)) != from {
+            return Ok(());
+        }
+
+        if !modified {
+            stderr.status(
+                "Rewriting",
+                format!("path dependencies in `{}`", manifest_path.display()),
+            )?;
+            modified = true;
+        }
+        let new_path = crate::manifest::relative_path(package_root, to)?;
+        crate::manifest::replace_str(&mut dep["path"], &new_path);
+
+        if let Some((old_name, new_name, strategy)) = rename {
+            if dep.at("package").as_str() == Some(old_name) {
+                crate::manifest::replace_str(&mut dep["package"], new_name);
+                stderr.status("Renaming", format!("`{}.package` to {:?}", key, new_name))?;
+            } else if strategy == RenameStrategy::Alias {
+                crate::manifest::insert_str(dep, "package", new_name);
+                stderr.status("Adding", format!("`package = {:?}` to `{}`", new_name, key))?;
+            } else {
+                ensure!(
+                    crate::manifest::rename_key(deps, key, new_name),
+                    "`{}` already has a dependency named {:?}",
+                    manifest_path.display(),
+                    new_name,
+                );
+                renamed_keys.push(key.to_owned());
+                stderr.status(
+                    "Renaming",
+                    format!("dependency {:?} to {:?}", key, new_name),
+                )?;
+            }
+        }
+        Ok(())
+    })?;
+
+    if let Some((_, new_name, _)) = rename {
+        for old_key in renamed_keys {
+            crate::manifest::rename_dependency_in_features(&mut cargo_toml, &old_key, new_name);
+        }
+    }
+
+    if modified {
+        let cargo_toml = cargo_toml.to_string();
+        if dry_run {
+            crate::fs::dry_run_diff(&manifest_path, &manifest_path, &orig);
+        }
+        crate::fs::write(manifest_path, cargo_toml, dry_run)?;
+    }
+    Ok(modified)
+}
+
+/// Finds the nearest ancestor of `path` whose manifest has a `[workspace]` table, as Cargo does.
+fn find_workspace_root(path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    for dir in path.ancestors().skip(1) {
+        let manifest_path = dir.join("Cargo.toml");
+        if manifest_path.exists()
+            && !crate::fs::read_toml_edit(&manifest_path)?
+                .at("workspace")
+                .is_none()
+        {
+            return Ok(Some(dir.to_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// The files in the workspace root that most commands modify.
+fn workspace_files(workspace_root: &Path) -> [PathBuf; 2] {
+    [
+        workspace_root.join("Cargo.toml"),
+        workspace_root.join("Cargo.lock"),
+    ]
+}
+
+fn is_empty_workspace(manifest_path: &Path) -> anyhow::Result<bool> {
+    return {
+        let CargoToml { workspace, package } = crate::fs::read_toml(manifest_path)?;
+        Ok(!workspace.members.is_empty() || package.is_some())
+    };
+
+    #[derive(Deserialize)]
+    struct CargoToml {
+        #[serde(default)]
         workspace: CargoTomlWorkspace,
         package: Option<toml::Value>,
     }
@@ -1067,6 +4011,27 @@ fn is_empty_workspace(manifest_path: &Path) -> anyhow::Result<bool> {
     }
 }
 
+/// Runs `cargo metadata` to update `Cargo.lock`, unless the update is deferred by `Plan`.
+fn update_lockfile(
+    workspace_root: &Path,
+    offline: bool,
+    mut stderr: impl WriteColor,
+) -> anyhow::Result<()> {
+    if crate::fs::defer_lock_update(workspace_root) {
+        return Ok(());
+    }
+    crate::fs::run_pending_checks()?;
+    stderr.status("Updating", workspace_root.join("Cargo.lock").display())?;
+    cargo_metadata(
+        Some(&workspace_root.join("Cargo.toml")),
+        false,
+        false,
+        offline,
+        workspace_root,
+    )?;
+    Ok(())
+}
+
 fn cargo_metadata(
     manifest_path: Option<&Path>,
     frozen: bool,
@@ -1074,6 +4039,11 @@ fn cargo_metadata(
     offline: bool,
     cwd: &Path,
 ) -> anyhow::Result<Metadata> {
+    if !(frozen || locked) {
+        let dir = manifest_path.and_then(Path::parent).unwrap_or(cwd);
+        crate::fs::track(dir.join("Cargo.lock"))?;
+    }
+
     let mut cmd = MetadataCommand::new();
     if let Some(manifest_path) = manifest_path {
         cmd.manifest_path(manifest_path);
@@ -1088,19 +4058,83 @@ fn cargo_metadata(
         cmd.other_options(vec!["--locked".to_owned()]);
     }
     let metadata = cmd.current_dir(cwd).exec().map_err(|err| match err {
-        cargo_metadata::Error::CargoMetadata { stderr } => anyhow!("{}", stderr.trim_end()),
-        err => err.into(),
+        cargo_metadata::Error::CargoMetadata { stderr } => Error::Cargo {
+            args: vec!["metadata".to_owned()],
+            message: stderr.trim_end().to_owned(),
+        }
+        .into(),
+        err => anyhow::Error::from(err),
     })?;
     debug!("workspace-root: {}", metadata.workspace_root.display());
     Ok(metadata)
 }
 
+/// Writes the current default members to `workspace.default-members` if it is missing, so that
+/// making one package (not) a default member leaves the others as they are.
+fn seed_default_members(
+    workspace_root: &Path,
+    adding: bool,
+    dry_run: bool,
+    mut stderr: impl WriteColor,
+) -> anyhow::Result<bool> {
+    let manifest_path = workspace_root.join("Cargo.toml");
+    let mut cargo_toml = crate::fs::read_toml_edit(&manifest_path)?;
+    if !cargo_toml.at("workspace").at("default-members").is_none() {
+        return Ok(false);
+    }
+
+    // Without `default-members`, only the root package is built if there is one.
+    let current = if cargo_toml.at("package").is_table_like() {
+        if !adding {
+            return Ok(false);
+        }
+        vec![".".to_owned()]
+    } else {
+        cargo_toml
+            .at("workspace")
+            .at("members")
+            .as_array()
+            .into_iter()
+            .flat_map(toml_edit::Array::iter)
+            .flat_map(toml_edit::Value::as_str)
+            .map(ToOwned::to_owned)
+            .collect()
+    };
+    if current.is_empty() {
+        return Ok(false);
+    }
+
+    let orig = cargo_toml.to_string();
+    cargo_toml["workspace"]["default-members"] =
+        toml_edit::value(current.iter().collect::<toml_edit::Array>());
+    stderr.status(
+        "Adding",
+        format!(
+            "`workspace.default-members` with the current default members [{}]",
+            current
+                .iter()
+                .format_with(", ", |s, f| f(&format_args!("{:?}", s))),
+        ),
+    )?;
+
+    let cargo_toml = cargo_toml.to_string();
+    if dry_run {
+        crate::fs::dry_run_diff(&manifest_path, &manifest_path, &orig);
+    }
+    crate::fs::write(&manifest_path, cargo_toml, dry_run)?;
+    Ok(true)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn modify_members<'a>(
     possibly_empty_workspace_root: &Path,
     add_to_workspace_members: &[&'a Path],
     add_to_workspace_exclude: &[&'a Path],
     rm_from_workspace_members: &[&'a Path],
     rm_from_workspace_exclude: &[&'a Path],
+    add_to_workspace_default_members: &[&'a Path],
+    rm_from_workspace_default_members: &[&'a Path],
+    glob_strategy: Option<GlobStrategy>,
     dry_run: bool,
     mut stderr: impl WriteColor,
 ) -> anyhow::Result<bool> {
@@ -1109,22 +4143,139 @@ fn modify_members<'a>(
         add_to_workspace_exclude,
         rm_from_workspace_members,
         rm_from_workspace_exclude,
+        add_to_workspace_default_members,
+        rm_from_workspace_default_members,
     ]
     .iter()
     .copied()
     .flatten()
     .any(|&p| p == possibly_empty_workspace_root)
     {
-        bail!(
-            "`{}` is the workspace root",
-            possibly_empty_workspace_root.display()
-        );
+        return Err(Error::WorkspaceRoot {
+            path: possibly_empty_workspace_root.to_owned(),
+        }
+        .into());
     }
 
     let manifest_path = possibly_empty_workspace_root.join("Cargo.toml");
     let mut cargo_toml = crate::fs::read_toml_edit(&manifest_path)?;
     let orig = cargo_toml.to_string();
 
+    let relative_to_root = |path: &'a Path| -> _ {
+        let path = path
+            .strip_prefix(possibly_empty_workspace_root)
+            .unwrap_or(path);
+        path.to_str()
+            .with_context(|| format!("{:?} is not valid UTF-8 path", path))
+    };
+
+    let same_paths = |value: &toml_edit::Value, target: &str| -> _ {
+        value.as_str().is_some_and(|s| {
+            possibly_empty_workspace_root.join(s) == possibly_empty_workspace_root.join(target)
+        })
+    };
+
+    let covered_by_glob = |value: &toml_edit::Value, target: &Path| -> _ {
+        value
+            .as_str()
+            .is_some_and(|s| is_glob(s) && glob_matches(possibly_empty_workspace_root, s, target))
+    };
+
+    let mut add_to_workspace_exclude = add_to_workspace_exclude.to_owned();
+    let mut rm_from_workspace_exclude = rm_from_workspace_exclude.to_owned();
+    let rm_from_workspace_default_members = rm_from_workspace_members
+        .iter()
+        .chain(rm_from_workspace_default_members)
+        .copied()
+        .unique()
+        .collect::<Vec<_>>();
+
+    if let Some(glob_strategy) = glob_strategy {
+        let globs = cargo_toml
+            .at("workspace")
+            .at("members")
+            .as_array()
+            .into_iter()
+            .flat_map(toml_edit::Array::iter)
+            .flat_map(toml_edit::Value::as_str)
+            .filter(|s| is_glob(s))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+
+        for glob in globs {
+            let pattern = glob_pattern(possibly_empty_workspace_root, &glob)?;
+            let carved = rm_from_workspace_members
+                .iter()
+                .copied()
+                .filter(|p| pattern.matches_path_with(p, GLOB_MATCH_OPTIONS))
+                .collect::<Vec<_>>();
+            if carved.is_empty() {
+                continue;
+            }
+
+            match glob_strategy {
+                GlobStrategy::Exclude => {
+                    for carved in carved {
+                        if !add_to_workspace_exclude.contains(&carved) {
+                            add_to_workspace_exclude.push(carved);
+                        }
+                        rm_from_workspace_exclude.retain(|&p| p != carved);
+                    }
+                }
+                GlobStrategy::Expand => {
+                    let excluded = cargo_toml
+                        .at("workspace")
+                        .at("exclude")
+                        .as_array()
+                        .into_iter()
+                        .flat_map(toml_edit::Array::iter)
+                        .flat_map(toml_edit::Value::as_str)
+                        .map(|s| possibly_empty_workspace_root.join(s))
+                        .collect::<Vec<_>>();
+
+                    let expanded = expand_glob(possibly_empty_workspace_root, &glob)?
+                        .into_iter()
+                        .filter(|p| {
+                            !carved.contains(&&**p) && !excluded.iter().any(|e| p.starts_with(e))
+                        })
+                        .map(|p| {
+                            let p = p.strip_prefix(possibly_empty_workspace_root)?;
+                            p.to_str()
+                                .map(ToOwned::to_owned)
+                                .with_context(|| format!("{:?} is not valid UTF-8 path", p))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+
+                    let array = cargo_toml["workspace"]["members"]
+                        .as_array_mut()
+                        .expect("should be an array");
+                    let i = array
+                        .iter()
+                        .position(|m| m.as_str() == Some(&glob))
+                        .expect("should contain the glob");
+                    array.remove(i);
+                    let mut i = i;
+                    for literal in &expanded {
+                        if array.iter().all(|m| !same_paths(m, literal)) {
+                            array.insert(i, &**literal);
+                            i += 1;
+                        }
+                    }
+                    stderr.status(
+                        "Expanding",
+                        format!(
+                            "{:?} into [{}] in `workspace.members`",
+                            glob,
+                            expanded
+                                .iter()
+                                .format_with(", ", |s, f| f(&format_args!("{:?}", s))),
+                        ),
+                    )?;
+                }
+            }
+        }
+    }
+
     for (field, add, rm) in &[
         (
             "members",
@@ -1133,46 +4284,94 @@ fn modify_members<'a>(
         ),
         (
             "exclude",
-            add_to_workspace_exclude,
-            rm_from_workspace_exclude,
+            &*add_to_workspace_exclude,
+            &*rm_from_workspace_exclude,
+        ),
+        (
+            "default-members",
+            add_to_workspace_default_members,
+            &*rm_from_workspace_default_members,
         ),
     ] {
-        let relative_to_root = |path: &'a Path| -> _ {
-            let path = path
-                .strip_prefix(possibly_empty_workspace_root)
-                .unwrap_or(path);
-            path.to_str()
-                .with_context(|| format!("{:?} is not valid UTF-8 path", path))
-        };
-
-        let same_paths = |value: &toml_edit::Value, target: &str| -> _ {
-            value.as_str().map_or(false, |s| {
-                possibly_empty_workspace_root.join(s) == possibly_empty_workspace_root.join(target)
-            })
-        };
+        // Creating an empty `workspace.default-members` would make the workspace have no default
+        // members.
+        if *field == "default-members"
+            && add.is_empty()
+            && cargo_toml.at("workspace").at(field).is_none()
+        {
+            continue;
+        }
 
         let array = cargo_toml["workspace"][field]
             .or_insert(toml_edit::value(toml_edit::Array::default()))
             .as_array_mut()
-            .with_context(|| format!("`workspace.{}` must be an array", field))?;
-        for add in *add {
-            let add = relative_to_root(add)?;
-            if array.iter().all(|m| !same_paths(m, add)) {
-                if !dry_run {
-                    array
-                        .push(add)
-                        .map_err(|_| anyhow!("`workspace.{}` must be an string array", field))?;
+            .ok_or_else(|| Error::NotAnArray {
+                field: (*field).to_owned(),
+            })?;
+
+        // A glob in `workspace.default-members` matching a package to remove is expanded into the
+        // other packages it matches.
+        if *field == "default-members" {
+            let globs = array
+                .iter()
+                .filter(|m| rm.iter().any(|rm| covered_by_glob(m, rm)))
+                .flat_map(toml_edit::Value::as_str)
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+            for glob in globs {
+                let expanded = expand_glob(possibly_empty_workspace_root, &glob)?
+                    .into_iter()
+                    .filter(|p| !rm.contains(&&**p))
+                    .map(|p| {
+                        let p = p.strip_prefix(possibly_empty_workspace_root)?;
+                        p.to_str()
+                            .map(ToOwned::to_owned)
+                            .with_context(|| format!("{:?} is not valid UTF-8 path", p))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let i = array
+                    .iter()
+                    .position(|m| m.as_str() == Some(&glob))
+                    .expect("should contain the glob");
+                array.remove(i);
+                let mut i = i;
+                for literal in &expanded {
+                    if array.iter().all(|m| !same_paths(m, literal)) {
+                        array.insert(i, &**literal);
+                        i += 1;
+                    }
                 }
+                stderr.status(
+                    "Expanding",
+                    format!(
+                        "{:?} into [{}] in `workspace.default-members`",
+                        glob,
+                        expanded
+                            .iter()
+                            .format_with(", ", |s, f| f(&format_args!("{:?}", s))),
+                    ),
+                )?;
+            }
+        }
+
+        let mut remaining = array.len();
+        for &add_path in *add {
+            let add = relative_to_root(add_path)?;
+            if array.iter().all(|m| {
+                !(same_paths(m, add) || (*field != "exclude" && covered_by_glob(m, add_path)))
+            }) {
+                array.push(add);
+                remaining += 1;
                 stderr.status("Adding", format!("{:?} to `workspace.{}`", add, field))?;
             }
         }
+        let before_removal = remaining;
         for rm in *rm {
             let rm = relative_to_root(rm)?;
             let i = array.iter().position(|m| same_paths(m, rm));
             if let Some(i) = i {
-                if !dry_run {
-                    array.remove(i);
-                }
+                array.remove(i);
+                remaining -= 1;
                 stderr.status_with_color(
                     "Removing",
                     format!("{:?} from `workspace.{}`", rm, field),
@@ -1180,16 +4379,147 @@ fn modify_members<'a>(
                 )?;
             }
         }
+
+        if *field == "default-members" && remaining == 0 && remaining < before_removal {
+            if let Some(workspace) = cargo_toml["workspace"].as_table_mut() {
+                workspace.remove(field);
+            }
+            stderr.status_with_color(
+                "Removing",
+                format!("`workspace.{}` as it got empty", field),
+                termcolor::Color::Red,
+            )?;
+        }
     }
 
     let cargo_toml = cargo_toml.to_string();
     let modified = cargo_toml != orig;
     if modified {
-        crate::fs::write(manifest_path, cargo_toml, dry_run)?;
+        if dry_run {
+            crate::fs::dry_run_diff(&manifest_path, &manifest_path, &orig);
+        }
+        crate::fs::write(&manifest_path, cargo_toml, dry_run)?;
+
+        // Members without manifests are only added with `--force` or right before `cargo new`.
+        if !dry_run
+            && add_to_workspace_members
+                .iter()
+                .all(|p| p.join("Cargo.toml").exists())
+        {
+            crate::fs::check_before_commit(&manifest_path, validate_workspace)?;
+        }
     }
     Ok(modified)
 }
 
+/// Runs `cargo metadata --no-deps` so that a manifest Cargo rejects is rolled back instead of
+/// being left on disk.
+fn validate_workspace(manifest_path: &Path) -> anyhow::Result<()> {
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .other_options(vec!["--offline".to_owned()])
+        .exec()
+        .map_err(|err| match err {
+            cargo_metadata::Error::CargoMetadata { stderr } => Error::Cargo {
+                args: vec!["metadata".to_owned(), "--no-deps".to_owned()],
+                message: format!(
+                    "Cargo rejected the modified `{}`\n\n{}",
+                    manifest_path.display(),
+                    stderr.trim_end(),
+                ),
+            }
+            .into(),
+            err => anyhow::Error::from(err),
+        })?;
+    Ok(())
+}
+
+/// Runs `cargo metadata --no-deps`, which neither resolves dependencies nor writes `Cargo.lock`.
+fn cargo_metadata_no_deps(workspace_root: &Path, offline: bool) -> anyhow::Result<Metadata> {
+    let mut cmd = MetadataCommand::new();
+    cmd.manifest_path(workspace_root.join("Cargo.toml"))
+        .current_dir(workspace_root)
+        .no_deps();
+    if offline {
+        cmd.other_options(vec!["--offline".to_owned()]);
+    }
+    cmd.exec().map_err(|err| match err {
+        cargo_metadata::Error::CargoMetadata { stderr } => Error::Cargo {
+            args: vec!["metadata".to_owned(), "--no-deps".to_owned()],
+            message: stderr.trim_end().to_owned(),
+        }
+        .into(),
+        err => anyhow::Error::from(err),
+    })
+}
+
+/// The target directory of the workspace at `workspace_root`, as `CARGO_TARGET_DIR` and
+/// `build.target-dir` set it. `<workspace root>/target` if `cargo metadata` fails.
+fn target_dir(workspace_root: &Path) -> PathBuf {
+    cargo_metadata_no_deps(workspace_root, true)
+        .map(|metadata| metadata.target_directory)
+        .unwrap_or_else(|err| {
+            debug!("{:#}", err);
+            workspace_root.join("target")
+        })
+}
+
+/// The directories of the workspace members.
+fn member_dirs(metadata: &Metadata) -> Vec<PathBuf> {
+    metadata
+        .workspace_members
+        .iter()
+        .map(|id| {
+            metadata[id]
+                .manifest_path
+                .parent()
+                .expect(r#"`manifest_path` should end with "Cargo.toml""#)
+                .to_owned()
+        })
+        .collect()
+}
+
+fn is_glob(member: &str) -> bool {
+    member.contains(&['*', '?', '['][..])
+}
+
+/// `*` and `?` do not match `/`, as Cargo expands the globs one path component at a time.
+const GLOB_MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// `member` under `workspace_root`, with the metacharacters in `workspace_root` escaped.
+fn glob_path(workspace_root: &Path, member: &str) -> PathBuf {
+    Path::new(&glob::Pattern::escape(&workspace_root.to_string_lossy())).join(member)
+}
+
+fn glob_pattern(workspace_root: &Path, member: &str) -> anyhow::Result<glob::Pattern> {
+    let pattern = glob_path(workspace_root, member);
+    glob::Pattern::new(&pattern.to_string_lossy())
+        .with_context(|| format!("invalid glob pattern: {:?}", member))
+}
+
+fn glob_matches(workspace_root: &Path, member: &str, path: &Path) -> bool {
+    glob_pattern(workspace_root, member)
+        .is_ok_and(|p| p.matches_path_with(path, GLOB_MATCH_OPTIONS))
+}
+
+/// Expands a glob in `workspace.members` as Cargo does: only directories containing a
+/// `Cargo.toml` are taken.
+fn expand_glob(workspace_root: &Path, member: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let pattern = glob_path(workspace_root, member);
+    let mut paths = glob::glob_with(&pattern.to_string_lossy(), GLOB_MATCH_OPTIONS)
+        .with_context(|| format!("invalid glob pattern: {:?}", member))?
+        .map(|p| p.with_context(|| format!("failed to expand {:?}", member)))
+        .filter(|p| p.as_ref().map_or(true, |p| p.join("Cargo.toml").exists()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
 trait WriteColorExt: WriteColor {
     fn warn(&mut self, message: impl Display) -> io::Result<()> {
         self.set_color(
@@ -1225,6 +4555,103 @@ trait WriteColorExt: WriteColor {
         writeln!(self, " {}", message)?;
         self.flush()
     }
+
+    /// Prints the diffs recorded in a dry run. Does nothing in nested commands.
+    fn dry_run_diffs(&mut self) -> io::Result<()> {
+        for (orig_path, orig, path, modified) in crate::fs::take_dry_run_diffs() {
+            self.diff(&orig_path, &orig, &path, &modified)?;
+        }
+        Ok(())
+    }
+
+    fn diff(
+        &mut self,
+        orig_path: &Path,
+        orig: &str,
+        modified_path: &Path,
+        modified: &str,
+    ) -> io::Result<()> {
+        const CONTEXT: usize = 3;
+
+        let mut lines = difference::Changeset::new(orig, modified, "\n")
+            .diffs
+            .into_iter()
+            .flat_map(|diff| {
+                let (tag, lines) = match diff {
+                    difference::Difference::Same(lines) => (' ', lines),
+                    difference::Difference::Add(lines) => ('+', lines),
+                    difference::Difference::Rem(lines) => ('-', lines),
+                };
+                lines
+                    .split('\n')
+                    .map(|line| (tag, line.to_owned()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        if lines
+            .last()
+            .is_some_and(|(tag, line)| *tag == ' ' && line.is_empty())
+        {
+            lines.pop();
+        }
+
+        let mut hunks = vec![];
+        for (i, _) in lines.iter().enumerate().filter(|(_, (tag, _))| *tag != ' ') {
+            let (start, end) = (
+                i.saturating_sub(CONTEXT),
+                (i + CONTEXT + 1).min(lines.len()),
+            );
+            match hunks.last_mut() {
+                Some((_, prev_end)) if start <= *prev_end => *prev_end = end,
+                _ => hunks.push((start, end)),
+            }
+        }
+        if hunks.is_empty() {
+            return Ok(());
+        }
+
+        self.set_color(ColorSpec::new().set_bold(true).set_reset(false))?;
+        writeln!(self, "--- {}", orig_path.display())?;
+        writeln!(self, "+++ {}", modified_path.display())?;
+        self.reset()?;
+
+        let count = |lines: &[(char, String)], excluded: char| -> _ {
+            lines.iter().filter(|(tag, _)| *tag != excluded).count()
+        };
+        for (start, end) in hunks {
+            let hunk = &lines[start..end];
+            let (orig_start, orig_len) = (count(&lines[..start], '+'), count(hunk, '+'));
+            let (modified_start, modified_len) = (count(&lines[..start], '-'), count(hunk, '-'));
+
+            self.set_color(
+                ColorSpec::new()
+                    .set_fg(Some(termcolor::Color::Cyan))
+                    .set_reset(false),
+            )?;
+            writeln!(
+                self,
+                "@@ -{},{} +{},{} @@",
+                orig_start + usize::from(orig_len > 0),
+                orig_len,
+                modified_start + usize::from(modified_len > 0),
+                modified_len,
+            )?;
+            self.reset()?;
+
+            for (tag, line) in hunk {
+                let color = match tag {
+                    '+' => Some(termcolor::Color::Green),
+                    '-' => Some(termcolor::Color::Red),
+                    _ => None,
+                };
+                self.set_color(ColorSpec::new().set_fg(color).set_reset(false))?;
+                write!(self, "{}{}", tag, line)?;
+                self.reset()?;
+                writeln!(self)?;
+            }
+        }
+        self.flush()
+    }
 }
 
 impl<W: WriteColor> WriteColorExt for W {}
@@ -1257,7 +4684,11 @@ impl Metadata {
         let stdout = str::from_utf8(&output.stdout)?.trim_end();
         let stderr = str::from_utf8(&output.stderr)?.trim_end();
         if !output.status.success() {
-            bail!("{}", stderr.trim_start_matches("error: "));
+            return Err(Error::Cargo {
+                args: vec!["pkgid".to_owned()],
+                message: stderr.trim_start_matches("error: ").to_owned(),
+            }
+            .into());
         }
 
         let url = stdout.parse::<Url>()?;
@@ -1276,9 +4707,11 @@ impl Metadata {
             .find(|Package { id, name, .. }| {
                 self.workspace_members.contains(id) && name == spec_name
             })
-            .with_context(|| {
-                let spec = spec.expect("should be present here");
-                format!("package `{}` is not a member of the workspace", spec)
+            .ok_or_else(|| {
+                Error::NotAMember {
+                    spec: spec.expect("should be present here").to_owned(),
+                }
+                .into()
             })
     }
 }
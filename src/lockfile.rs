@@ -0,0 +1,173 @@
+use crate::manifest::TableExt as _;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    path::Path,
+};
+
+#[derive(Deserialize)]
+struct Lockfile {
+    version: Option<i64>,
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+impl LockedPackage {
+    fn id(&self) -> (&str, &str, Option<&str>) {
+        (&self.name, &self.version, self.source.as_deref())
+    }
+}
+
+/// Copies the non-path packages that the path package `name` depends on from the lock file `from`
+/// to `to`, so that Cargo keeps the same versions when `name` joins the workspace of `to`.
+///
+/// Returns the number of copied packages.
+pub(crate) fn migrate(from: &Path, to: &Path, name: &str, dry_run: bool) -> anyhow::Result<usize> {
+    if !from.exists() {
+        return Ok(0);
+    }
+    let from = crate::fs::read_toml::<Lockfile, _>(from)?;
+
+    let find = |dep: &str| -> Option<&LockedPackage> {
+        let mut words = dep.splitn(3, ' ');
+        let name = words.next()?;
+        let version = words.next();
+        from.package
+            .iter()
+            .find(|p| p.name == name && version.is_none_or(|v| p.version == v))
+    };
+
+    let mut reachable = BTreeSet::new();
+    let mut queue = from
+        .package
+        .iter()
+        .filter(|p| p.name == name && p.source.is_none())
+        .collect::<VecDeque<_>>();
+    while let Some(package) = queue.pop_front() {
+        for dep in package.dependencies.iter().flat_map(|d| find(d)) {
+            if reachable.insert(dep.id()) {
+                queue.push_back(dep);
+            }
+        }
+    }
+    let migrated = from
+        .package
+        .iter()
+        .filter(|p| p.source.is_some() && reachable.contains(&p.id()))
+        .collect::<Vec<_>>();
+
+    let mut lock = if to.exists() {
+        crate::fs::read_toml_edit(to)?
+    } else {
+        let mut lock = toml_edit::DocumentMut::new();
+        if let Some(version) = from.version {
+            lock["version"] = toml_edit::value(version);
+        }
+        lock
+    };
+
+    let existing = lock
+        .at("package")
+        .as_array_of_tables()
+        .into_iter()
+        .flat_map(|packages| packages.iter())
+        .flat_map(|p| {
+            let name = p.at("name").as_str()?.to_owned();
+            let version = p.at("version").as_str()?.to_owned();
+            let source = p.at("source").as_str().map(ToOwned::to_owned);
+            Some((name, version, source))
+        })
+        .collect::<Vec<_>>();
+
+    let migrated = migrated
+        .into_iter()
+        .filter(|p| {
+            !existing
+                .iter()
+                .any(|(n, v, s)| (&**n, &**v, s.as_deref()) == p.id())
+        })
+        .collect::<Vec<_>>();
+    if migrated.is_empty() {
+        return Ok(0);
+    }
+
+    if lock.at("package").is_none() {
+        lock["package"] = toml_edit::Item::ArrayOfTables(toml_edit::ArrayOfTables::new());
+    }
+    let packages = lock["package"]
+        .as_array_of_tables_mut()
+        .ok_or_else(|| anyhow::anyhow!("`package` in `{}` must be an array", to.display()))?;
+
+    // Dependencies without versions become ambiguous if another version of the same package is
+    // added.
+    let mut versions = HashMap::<_, Vec<_>>::new();
+    for (name, version, _) in &existing {
+        versions
+            .entry(name.clone())
+            .or_default()
+            .push(version.clone());
+    }
+    for package in &migrated {
+        versions
+            .entry(package.name.clone())
+            .or_default()
+            .push(package.version.clone());
+    }
+    for i in 0..packages.len() {
+        let package = packages.get_mut(i).expect("`i` should be in bounds");
+        if let Some(deps) = package["dependencies"].as_array_mut() {
+            for j in 0..deps.len() {
+                let qualified =
+                    deps.get(j).and_then(|d| d.as_str()).and_then(|dep| {
+                        match &versions.get(dep)?[..] {
+                            [_, _, ..] => existing
+                                .iter()
+                                .find(|(n, _, _)| n == dep)
+                                .map(|(_, v, _)| format!("{} {}", dep, v)),
+                            _ => None,
+                        }
+                    });
+                if let Some(qualified) = qualified {
+                    deps.replace(j, qualified);
+                }
+            }
+        }
+    }
+
+    for package in &migrated {
+        let mut table = toml_edit::Table::new();
+        table["name"] = toml_edit::value(&*package.name);
+        table["version"] = toml_edit::value(&*package.version);
+        if let Some(source) = &package.source {
+            table["source"] = toml_edit::value(&**source);
+        }
+        if let Some(checksum) = &package.checksum {
+            table["checksum"] = toml_edit::value(&**checksum);
+        }
+        if !package.dependencies.is_empty() {
+            let mut deps = toml_edit::Array::default();
+            for dep in &package.dependencies {
+                let dep = match find(dep) {
+                    Some(dep) => format!("{} {}", dep.name, dep.version),
+                    None => dep.clone(),
+                };
+                deps.push(dep);
+            }
+            table["dependencies"] = toml_edit::value(deps);
+        }
+        packages.push(table);
+    }
+
+    crate::fs::write(to, lock.to_string(), dry_run)?;
+    Ok(migrated.len())
+}
@@ -0,0 +1,672 @@
+use cargo_metadata::DependencyKind;
+use easy_ext::ext;
+use std::path::{Component, Path, PathBuf};
+
+static NONE: toml_edit::Item = toml_edit::Item::None;
+
+#[ext(ItemExt)]
+impl toml_edit::Item {
+    /// `self[key]` that returns `Item::None` instead of panicking if the key is missing.
+    pub(crate) fn at(&self, key: &str) -> &toml_edit::Item {
+        self.get(key).unwrap_or(&NONE)
+    }
+}
+
+#[ext(TableExt)]
+impl toml_edit::Table {
+    /// `self[key]` that returns `Item::None` instead of panicking if the key is missing.
+    pub(crate) fn at(&self, key: &str) -> &toml_edit::Item {
+        self.get(key).unwrap_or(&NONE)
+    }
+}
+
+/// Calls `f` with the table and the key of every dependency declared in `[dependencies]`,
+/// `[dev-dependencies]`, `[build-dependencies]` and `[target.*.*]`.
+pub(crate) fn for_each_dependency(
+    manifest: &mut toml_edit::DocumentMut,
+    mut f: impl FnMut(DependencyKind, &mut toml_edit::Table, &str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    visit_dependency_tables(manifest.as_table_mut(), &mut f)?;
+
+    if let Some(target) = manifest
+        .get_mut("target")
+        .and_then(toml_edit::Item::as_table_mut)
+    {
+        let cfgs = target
+            .iter()
+            .map(|(cfg, _)| cfg.to_owned())
+            .collect::<Vec<_>>();
+        for cfg in cfgs {
+            if let Some(platform) = target[&*cfg].as_table_mut() {
+                visit_dependency_tables(platform, &mut f)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn visit_dependency_tables(
+    table: &mut toml_edit::Table,
+    f: &mut impl FnMut(DependencyKind, &mut toml_edit::Table, &str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    for &(key, kind) in &[
+        ("dependencies", DependencyKind::Normal),
+        ("dev-dependencies", DependencyKind::Development),
+        ("dev_dependencies", DependencyKind::Development),
+        ("build-dependencies", DependencyKind::Build),
+        ("build_dependencies", DependencyKind::Build),
+    ] {
+        let names = match table.get(key).and_then(toml_edit::Item::as_table) {
+            Some(deps) => deps
+                .iter()
+                .map(|(name, _)| name.to_owned())
+                .collect::<Vec<_>>(),
+            None => continue,
+        };
+        let deps = table[key].as_table_mut().expect("checked above");
+        for name in names {
+            f(kind, deps, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces a string value keeping its surrounding whitespace and comments.
+pub(crate) fn replace_str(item: &mut toml_edit::Item, new: &str) {
+    if let Some(value) = item.as_value_mut() {
+        let decor = value.decor().clone();
+        *value = new.into();
+        *value.decor_mut() = decor;
+    } else {
+        *item = toml_edit::value(new);
+    }
+}
+
+/// Sets a string value in a table or an inline table.
+pub(crate) fn insert_str(item: &mut toml_edit::Item, key: &str, value: &str) {
+    if let Some(table) = item.as_inline_table_mut() {
+        table.get_or_insert(key, value);
+        table.fmt();
+    } else {
+        item[key] = toml_edit::value(value);
+    }
+}
+
+/// Moves an item to another key. Returns `false` if `to` is already taken.
+pub(crate) fn rename_key(table: &mut toml_edit::Table, from: &str, to: &str) -> bool {
+    if table.contains_key(to) {
+        return false;
+    }
+    if let Some(item) = table.remove(from) {
+        table.insert(to, item);
+    }
+    true
+}
+
+/// Renames `old/feature`, `old?/feature`, `dep:old` and `old` in `[features]`. `old` is left as is
+/// if it is a feature of the package itself.
+pub(crate) fn rename_dependency_in_features(
+    manifest: &mut toml_edit::DocumentMut,
+    old: &str,
+    new: &str,
+) {
+    let features = match manifest
+        .get_mut("features")
+        .and_then(toml_edit::Item::as_table_mut)
+    {
+        Some(features) => features,
+        None => return,
+    };
+    let names = features
+        .iter()
+        .map(|(name, _)| name.to_owned())
+        .collect::<Vec<_>>();
+    let is_feature = names.iter().any(|name| name == old);
+    for name in names {
+        if let Some(array) = features[&*name].as_array_mut() {
+            for i in 0..array.len() {
+                let renamed = array
+                    .get(i)
+                    .and_then(toml_edit::Value::as_str)
+                    .and_then(|s| {
+                        if s.strip_prefix("dep:") == Some(old) {
+                            Some(format!("dep:{}", new))
+                        } else if s == old && !is_feature {
+                            Some(new.to_owned())
+                        } else {
+                            s.strip_prefix(old)
+                                .filter(|rest| rest.starts_with('/') || rest.starts_with("?/"))
+                                .map(|rest| format!("{}{}", new, rest))
+                        }
+                    });
+                if let Some(renamed) = renamed {
+                    array.replace(i, renamed);
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites the relative paths in the manifest of a package copied from `from` to `to` so that
+/// the ones pointing outside the package still resolve to the same files.
+///
+/// Returns `(key, old, new)` for each rewritten path.
+pub(crate) fn rebase_paths(
+    manifest: &mut toml_edit::DocumentMut,
+    from: &Path,
+    to: &Path,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    let mut rebased = vec![];
+
+    let mut rebase = |item: &mut toml_edit::Item, key: String| -> anyhow::Result<()> {
+        let old = match item.as_str() {
+            Some(old) if !Path::new(old).is_absolute() => old.to_owned(),
+            _ => return Ok(()),
+        };
+        let target = normalize(&from.join(&old));
+        if target.starts_with(from) {
+            return Ok(());
+        }
+        let new = relative_path(to, &target)?;
+        if new != old {
+            replace_str(item, &new);
+            rebased.push((key, old, new));
+        }
+        Ok(())
+    };
+
+    for key in &["build", "readme", "license-file", "license_file"] {
+        if manifest.at("package").at(key).is_str() {
+            rebase(&mut manifest["package"][*key], format!("package.{}", key))?;
+        }
+    }
+
+    if manifest.at("lib").at("path").is_str() {
+        rebase(&mut manifest["lib"]["path"], "lib.path".to_owned())?;
+    }
+
+    for target in &["bin", "example", "test", "bench"] {
+        if let Some(targets) = manifest
+            .get_mut(target)
+            .and_then(toml_edit::Item::as_array_of_tables_mut)
+        {
+            for i in 0..targets.len() {
+                let table = targets.get_mut(i).expect("`i` should be in bounds");
+                if table.at("path").is_str() {
+                    rebase(&mut table["path"], format!("{}[{}].path", target, i))?;
+                }
+            }
+        }
+    }
+
+    for_each_dependency(manifest, |_, deps, key| {
+        let dep = &mut deps[key];
+        if dep.at("path").is_str() {
+            rebase(&mut dep["path"], format!("{}.path", key))?;
+        }
+        Ok(())
+    })?;
+
+    Ok(rebased)
+}
+
+/// Removes `.` and `..` without accessing the file system.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut ret = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !ret.pop() {
+                    ret.push(component);
+                }
+            }
+            component => ret.push(component),
+        }
+    }
+    ret
+}
+
+/// Returns `to` relative to the directory `from`, with `/` as the separator.
+pub(crate) fn relative_path(from: &Path, to: &Path) -> anyhow::Result<String> {
+    let (from, to) = (normalize(from), normalize(to));
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut ret = vec![".."; from.components().count() - common];
+    for component in to.components().skip(common) {
+        let component = component.as_os_str();
+        ret.push(
+            component
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!("{:?} is not valid UTF-8", component))?,
+        );
+    }
+    Ok(if ret.is_empty() {
+        ".".to_owned()
+    } else {
+        ret.join("/")
+    })
+}
+
+/// A field of a package manifest that is inherited from `[workspace.*]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Inherited {
+    Package(String),
+    Dependency(String),
+    Lints,
+}
+
+impl Inherited {
+    /// The item in the workspace manifest that the field refers to.
+    fn get<'a>(&self, workspace: &'a toml_edit::DocumentMut) -> &'a toml_edit::Item {
+        match self {
+            Self::Package(key) => workspace.at("workspace").at("package").at(key),
+            Self::Dependency(key) => workspace.at("workspace").at("dependencies").at(key),
+            Self::Lints => workspace.at("workspace").at("lints"),
+        }
+    }
+}
+
+impl std::fmt::Display for Inherited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Package(key) => write!(f, "workspace.package.{}", key),
+            Self::Dependency(key) => write!(f, "workspace.dependencies.{}", key),
+            Self::Lints => write!(f, "workspace.lints"),
+        }
+    }
+}
+
+/// Returns the fields declared as `{ workspace = true }` or `*.workspace = true`.
+pub(crate) fn inherited_fields(
+    manifest: &mut toml_edit::DocumentMut,
+) -> anyhow::Result<Vec<Inherited>> {
+    let mut fields = manifest
+        .at("package")
+        .as_table()
+        .into_iter()
+        .flat_map(|package| package.iter())
+        .filter(|(_, item)| is_inherited(item))
+        .map(|(key, _)| Inherited::Package(key.to_owned()))
+        .collect::<Vec<_>>();
+
+    for_each_dependency(manifest, |_, deps, key| {
+        let field = Inherited::Dependency(key.to_owned());
+        if is_inherited(deps.at(key)) && !fields.contains(&field) {
+            fields.push(field);
+        }
+        Ok(())
+    })?;
+
+    if is_inherited(manifest.at("lints")) {
+        fields.push(Inherited::Lints);
+    }
+    Ok(fields)
+}
+
+fn is_inherited(item: &toml_edit::Item) -> bool {
+    item.at("workspace").as_bool() == Some(true)
+}
+
+/// Replaces the fields inherited from the workspace at `workspace_root` with the values in its
+/// manifest so that the package at `package_root` no longer depends on the workspace.
+///
+/// Returns the inlined fields.
+pub(crate) fn inline_inherited(
+    manifest: &mut toml_edit::DocumentMut,
+    workspace: &toml_edit::DocumentMut,
+    workspace_root: &Path,
+    package_root: &Path,
+) -> anyhow::Result<Vec<Inherited>> {
+    let fields = inherited_fields(manifest)?;
+
+    for field in &fields {
+        let mut value = field.get(workspace).clone();
+        if value.is_none() {
+            anyhow::bail!("`{}` is not defined in the workspace", field);
+        }
+        match field {
+            Inherited::Package(key) => {
+                if ["readme", "license-file", "license_file"].contains(&&**key) {
+                    rebase_value(&mut value, workspace_root, package_root)?;
+                }
+                let item = &mut manifest["package"][key.as_str()];
+                if let (Some(new), Some(old)) = (value.as_value_mut(), item.as_value()) {
+                    *new.decor_mut() = old.decor().clone();
+                }
+                *item = value;
+            }
+            Inherited::Dependency(key) => {
+                rebase_dependency(&mut value, workspace_root, package_root)?;
+                for_each_dependency(manifest, |_, deps, name| {
+                    let dep = &mut deps[name];
+                    if name == key && is_inherited(dep) {
+                        merge_dependency(dep, &value);
+                    }
+                    Ok(())
+                })?;
+            }
+            Inherited::Lints => manifest["lints"] = value,
+        }
+    }
+    Ok(fields)
+}
+
+/// Adds the fields inherited from the workspace manifest `src` to the workspace manifest `dst`,
+/// keeping the ones `dst` already defines.
+///
+/// Returns the added fields and the ones `dst` defines differently.
+pub(crate) fn add_inherited(
+    manifest: &mut toml_edit::DocumentMut,
+    src: &toml_edit::DocumentMut,
+    src_root: &Path,
+    dst: &mut toml_edit::DocumentMut,
+    dst_root: &Path,
+) -> anyhow::Result<(Vec<Inherited>, Vec<Inherited>)> {
+    let (mut added, mut conflicting) = (vec![], vec![]);
+
+    for field in inherited_fields(manifest)? {
+        let mut value = field.get(src).clone();
+        if value.is_none() {
+            anyhow::bail!("`{}` is not defined in the workspace", field);
+        }
+        match &field {
+            Inherited::Package(key)
+                if ["readme", "license-file", "license_file"].contains(&&**key) =>
+            {
+                rebase_value(&mut value, src_root, dst_root)?;
+            }
+            Inherited::Dependency(_) => rebase_dependency(&mut value, src_root, dst_root)?,
+            _ => {}
+        }
+
+        let existing = field.get(dst);
+        if !existing.is_none() {
+            if item_to_string(existing) != item_to_string(&value) {
+                conflicting.push(field);
+            }
+            continue;
+        }
+
+        if dst.at("workspace").is_none() {
+            dst["workspace"] = toml_edit::table();
+        }
+        match &field {
+            Inherited::Package(key) => {
+                if dst.at("workspace").at("package").is_none() {
+                    dst["workspace"]["package"] = toml_edit::table();
+                }
+                dst["workspace"]["package"][key.as_str()] = value;
+            }
+            Inherited::Dependency(key) => {
+                if dst.at("workspace").at("dependencies").is_none() {
+                    dst["workspace"]["dependencies"] = toml_edit::table();
+                }
+                let deps = dst["workspace"]["dependencies"]
+                    .as_table_mut()
+                    .ok_or_else(|| anyhow::anyhow!("`workspace.dependencies` must be a table"))?;
+                deps[key.as_str()] = value;
+            }
+            Inherited::Lints => dst["workspace"]["lints"] = value,
+        }
+        added.push(field);
+    }
+    Ok((added, conflicting))
+}
+
+fn item_to_string(item: &toml_edit::Item) -> String {
+    match item {
+        toml_edit::Item::Value(value) => value.to_string().trim().to_owned(),
+        toml_edit::Item::Table(table) => table.to_string().trim().to_owned(),
+        _ => String::new(),
+    }
+}
+
+/// Rewrites a relative path in `item` written relative to `from` to be relative to `to`.
+fn rebase_value(item: &mut toml_edit::Item, from: &Path, to: &Path) -> anyhow::Result<()> {
+    if let Some(old) = item.as_str().filter(|s| !Path::new(s).is_absolute()) {
+        let new = relative_path(to, &from.join(old))?;
+        replace_str(item, &new);
+    }
+    Ok(())
+}
+
+fn rebase_dependency(dep: &mut toml_edit::Item, from: &Path, to: &Path) -> anyhow::Result<()> {
+    if dep.at("path").is_str() {
+        if let Some(table) = dep.as_inline_table_mut() {
+            let old = table.get("path").and_then(toml_edit::Value::as_str);
+            if let Some(old) = old.filter(|s| !Path::new(s).is_absolute()) {
+                let new = relative_path(to, &from.join(old))?;
+                *table.get_mut("path").expect("checked above") = new.into();
+                table.fmt();
+            }
+        } else {
+            rebase_value(&mut dep["path"], from, to)?;
+        }
+    }
+    Ok(())
+}
+
+type DependencyEntries = Vec<(String, toml_edit::Value)>;
+
+/// Returns the keys and values of a dependency written as a string, an inline table or a table.
+fn dependency_entries(dep: &toml_edit::Item) -> DependencyEntries {
+    if let Some(version) = dep.as_value().filter(|v| v.is_str()) {
+        vec![("version".to_owned(), version.clone())]
+    } else if let Some(table) = dep.as_inline_table() {
+        table
+            .iter()
+            .map(|(key, value)| (key.to_owned(), value.clone()))
+            .collect()
+    } else if let Some(table) = dep.as_table() {
+        table
+            .iter()
+            .flat_map(|(key, item)| Some((key.to_owned(), item.as_value()?.clone())))
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+/// Replaces `{ workspace = true, .. }` with the entry in `[workspace.dependencies]`, keeping the
+/// other keys of the member and concatenating `features`.
+fn merge_dependency(dep: &mut toml_edit::Item, workspace_dep: &toml_edit::Item) {
+    let mut entries = dependency_entries(workspace_dep);
+
+    if let Some(table) = dep.as_inline_table_mut() {
+        table.remove("workspace");
+    } else if let Some(table) = dep.as_table_mut() {
+        table.remove("workspace");
+    }
+
+    for (key, value) in &mut entries {
+        if key == "features" {
+            if let (Some(features), Some(member_features)) =
+                (value.as_array_mut(), dep.at("features").as_array())
+            {
+                for feature in member_features.iter() {
+                    let name = feature.as_str();
+                    if !features.iter().any(|f| f.as_str() == name) {
+                        features.push(feature.clone());
+                    }
+                }
+            }
+        } else if !dep.at(key).is_none() {
+            continue;
+        }
+        if let Some(table) = dep.as_inline_table_mut() {
+            table.remove(key);
+            table.get_or_insert(key.as_str(), value.clone());
+        } else {
+            dep[key.as_str()] = toml_edit::value(value.clone());
+        }
+    }
+    if let Some(table) = dep.as_inline_table_mut() {
+        table.fmt();
+    }
+}
+
+/// Replaces fields and dependencies equal to the ones in `[workspace.package]` and
+/// `[workspace.dependencies]` of the workspace at `workspace_root` with `{ workspace = true }`.
+///
+/// Returns the replaced fields.
+pub(crate) fn inherit_from_workspace(
+    manifest: &mut toml_edit::DocumentMut,
+    workspace: &toml_edit::DocumentMut,
+    workspace_root: &Path,
+    package_root: &Path,
+) -> anyhow::Result<Vec<Inherited>> {
+    let mut inherited = vec![];
+
+    let keys = workspace
+        .at("workspace")
+        .at("package")
+        .as_table()
+        .into_iter()
+        .flat_map(|package| package.iter())
+        .map(|(key, _)| key.to_owned())
+        .collect::<Vec<_>>();
+    for key in keys {
+        let mut expected = workspace.at("workspace").at("package").at(&key).clone();
+        if ["readme", "license-file", "license_file"].contains(&&*key) {
+            rebase_value(&mut expected, workspace_root, package_root)?;
+        }
+        let actual = manifest.at("package").at(&key);
+        if actual.is_none() || is_inherited(actual) || actual.is_table() {
+            continue;
+        }
+        if item_to_string(actual) == item_to_string(&expected) {
+            let item = &mut manifest["package"][key.as_str()];
+            let decor = item.as_value().expect("checked above").decor().clone();
+            let mut value = toml_edit::Value::from(inherited_inline_table(&[]));
+            *value.decor_mut() = decor;
+            *item = toml_edit::Item::Value(value);
+            inherited.push(Inherited::Package(key));
+        }
+    }
+
+    for_each_dependency(manifest, |_, deps, key| {
+        let mut expected = workspace.at("workspace").at("dependencies").at(key).clone();
+        if expected.is_none() {
+            return Ok(());
+        }
+        rebase_dependency(&mut expected, workspace_root, package_root)?;
+
+        let dep = &mut deps[key];
+        if is_inherited(dep) {
+            return Ok(());
+        }
+        let (expected_source, _) = split_dependency(&expected);
+        let (source, rest) = split_dependency(dep);
+        let default_features = |rest: &DependencyEntries| {
+            rest.iter()
+                .find(|(k, _)| k == "default-features" || k == "default_features")
+                .and_then(|(_, v)| v.as_bool())
+                .unwrap_or(true)
+        };
+        let features = |rest: &DependencyEntries| {
+            rest.iter()
+                .find(|(k, _)| k == "features")
+                .and_then(|(_, v)| v.as_array())
+                .into_iter()
+                .flatten()
+                .flat_map(|f| f.as_str().map(ToOwned::to_owned))
+                .collect::<Vec<_>>()
+        };
+        let expected_rest = split_dependency(&expected).1;
+        // The features of the workspace entry are enabled for every member that inherits it.
+        let expected_features = features(&expected_rest);
+        if source != expected_source
+            || default_features(&rest) != default_features(&expected_rest)
+            || !expected_features
+                .iter()
+                .all(|f| features(&rest).contains(f))
+        {
+            return Ok(());
+        }
+        let mut rest = rest
+            .into_iter()
+            .filter(|(k, _)| k != "default-features" && k != "default_features")
+            .collect::<Vec<_>>();
+        // Only the features that the workspace entry does not enable are left in the member.
+        for (_, features) in rest.iter_mut().filter(|(k, _)| k == "features") {
+            if let Some(features) = features.as_array_mut() {
+                features.retain(|f| {
+                    !f.as_str()
+                        .is_some_and(|f| expected_features.iter().any(|e| e == f))
+                });
+                features.fmt();
+            }
+        }
+        rest.retain(|(k, v)| {
+            k != "features" || !v.as_array().is_some_and(toml_edit::Array::is_empty)
+        });
+
+        if let Some(table) = dep.as_table_mut() {
+            for (key, _) in &source {
+                table.remove(key);
+            }
+            for key in &["default-features", "default_features"] {
+                table.remove(key);
+            }
+            match rest.iter().find(|(k, _)| k == "features") {
+                Some((_, features)) => {
+                    if let Some(value) = table["features"].as_value_mut() {
+                        let decor = value.decor().clone();
+                        *value = features.clone();
+                        *value.decor_mut() = decor;
+                    }
+                }
+                None => {
+                    table.remove("features");
+                }
+            }
+            table["workspace"] = toml_edit::value(true);
+        } else {
+            let decor = dep.as_value().expect("should be a value").decor().clone();
+            let mut value = toml_edit::Value::from(inherited_inline_table(&rest));
+            *value.decor_mut() = decor;
+            *dep = toml_edit::Item::Value(value);
+        }
+        let field = Inherited::Dependency(key.to_owned());
+        if !inherited.contains(&field) {
+            inherited.push(field);
+        }
+        Ok(())
+    })?;
+
+    Ok(inherited)
+}
+
+/// Keys that decide where a dependency comes from.
+const DEPENDENCY_SOURCE_KEYS: &[&str] = &[
+    "version", "path", "git", "branch", "tag", "rev", "registry", "package",
+];
+
+/// Splits a dependency into the source keys and the others, as formatted strings for the former.
+fn split_dependency(dep: &toml_edit::Item) -> (Vec<(String, String)>, DependencyEntries) {
+    let (mut source, mut rest) = (vec![], vec![]);
+    for (key, value) in dependency_entries(dep) {
+        if DEPENDENCY_SOURCE_KEYS.contains(&&*key) {
+            source.push((key, value.to_string().trim().to_owned()));
+        } else {
+            rest.push((key, value));
+        }
+    }
+    source.sort();
+    (source, rest)
+}
+
+fn inherited_inline_table(rest: &[(String, toml_edit::Value)]) -> toml_edit::InlineTable {
+    let mut table = toml_edit::InlineTable::default();
+    table.get_or_insert("workspace", true);
+    for (key, value) in rest {
+        table.get_or_insert(key, value.clone());
+    }
+    table.fmt();
+    table
+}
@@ -0,0 +1,234 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_metadata::{Metadata, MetadataCommand};
+use difference::assert_diff;
+use duct::cmd;
+use std::{
+    env, fs, io,
+    path::Path,
+    str::{self, Utf8Error},
+};
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+#[test]
+fn adopt() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-adopt")?;
+    let (ws, ext) = (tempdir.path().join("ws"), tempdir.path().join("ext"));
+
+    fs::create_dir(&ws)?;
+    fs::write(ws.join("Cargo.toml"), ROOT_ORIGINAL)?;
+    cargo_new(&ws.join("a"))?;
+    cargo_new(&ext)?;
+    fs::write(ext.join("Cargo.toml"), EXT_ORIGINAL)?;
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(
+        &cargo_exe,
+        "update",
+        "-q",
+        "-p",
+        "glob",
+        "--precise",
+        "0.3.0"
+    )
+    .dir(&ext)
+    .run()?;
+    cargo_metadata(&ws.join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Adopt::new(&ws, &ext, &ws.join("ext"))
+        .inherit(true)
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&ws.join("Cargo.toml"), ROOT_EXPECTED)?;
+    assert_manifest(&ws.join("ext").join("Cargo.toml"), EXT_EXPECTED)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replace("{{ws}}", &ws.to_string_lossy())
+            .replace("{{ext}}", &ext.to_string_lossy()),
+    )?;
+    assert!(!ext.exists());
+    assert!(!ws.join("ext").join("Cargo.lock").exists());
+    cargo_metadata(&ws.join("Cargo.toml"), &["--locked"])?;
+    assert!(fs::read_to_string(ws.join("Cargo.lock"))?
+        .contains("name = \"glob\"\nversion = \"0.3.0\"\n"));
+    return Ok(());
+
+    static ROOT_ORIGINAL: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+
+[workspace.package]
+edition = "2018"
+
+[workspace.dependencies]
+glob = "0.3"
+"#;
+
+    static EXT_ORIGINAL: &str = r#"[package]
+name = "ext"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+glob = { version = "0.3", optional = true }
+
+[workspace]
+"#;
+
+    static ROOT_EXPECTED: &str = r#"[workspace]
+members = ["a", "ext"]
+exclude = []
+
+[workspace.package]
+edition = "2018"
+
+[workspace.dependencies]
+glob = "0.3"
+"#;
+
+    static EXT_EXPECTED: &str = r#"[package]
+name = "ext"
+version = "0.1.0"
+edition = { workspace = true }
+
+[dependencies]
+glob = { workspace = true, optional = true }
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{ext}}` to `{{ws}}/ext`
+       Found workspace at {{ws}}
+      Adding "ext" to `workspace.members`
+    Removing `[workspace]` from `{{ws}}/ext/Cargo.toml`
+  Inheriting `workspace.package.edition`
+  Inheriting `workspace.dependencies.glob`
+   Migrating 1 locked package(s) to `{{ws}}/Cargo.lock`
+    Removing `{{ws}}/ext/Cargo.lock`
+    Removing directory `{{ext}}`
+    Updating {{ws}}/Cargo.lock
+"#;
+}
+
+#[test]
+fn workspace_package() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-adopt-workspace-package")?;
+    let (ws, ext) = (tempdir.path().join("ws"), tempdir.path().join("ext"));
+
+    fs::create_dir(&ws)?;
+    cargo_new(&ws.join("a"))?;
+    fs::write(ws.join("Cargo.toml"), ROOT_ORIGINAL)?;
+    let mut a_manifest = fs::read_to_string(ws.join("a").join("Cargo.toml"))?;
+    a_manifest += "\n[features]\nx = []\n";
+    fs::write(ws.join("a").join("Cargo.toml"), a_manifest)?;
+    cargo_new(&ext)?;
+    fs::write(ext.join("Cargo.toml"), EXT_ORIGINAL)?;
+    cargo_metadata(&ext.join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Adopt::new(&ws, &ext, &ws.join("ext"))
+        .inherit(true)
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&ws.join("Cargo.toml"), ROOT_EXPECTED)?;
+    assert_manifest(&ws.join("ext").join("Cargo.toml"), EXT_EXPECTED)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replace("{{ws}}", &ws.to_string_lossy())
+            .replace("{{ext}}", &ext.to_string_lossy()),
+    )?;
+    cargo_metadata(&ws.join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ROOT_ORIGINAL: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+
+[workspace.dependencies]
+a = { path = "a", features = ["x"] }
+"#;
+
+    static EXT_ORIGINAL: &str = r#"[package]
+name = "ext"
+version = "0.1.0"
+edition = "2018"
+license = { workspace = true }
+
+[dependencies]
+a = { path = "../ws/a" }
+
+[workspace]
+
+[workspace.package]
+license = "MIT"
+"#;
+
+    static ROOT_EXPECTED: &str = r#"[workspace]
+members = ["a", "ext"]
+exclude = []
+
+[workspace.dependencies]
+a = { path = "a", features = ["x"] }
+
+[workspace.package]
+license = "MIT"
+"#;
+
+    static EXT_EXPECTED: &str = r#"[package]
+name = "ext"
+version = "0.1.0"
+edition = "2018"
+license = { workspace = true }
+
+[dependencies]
+a = { path = "../a" }
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{ext}}` to `{{ws}}/ext`
+    Rebasing `a.path` from "../ws/a" to "../a"
+      Adding `workspace.package.license`
+       Found workspace at {{ws}}
+      Adding "ext" to `workspace.members`
+    Removing `[workspace]` from `{{ws}}/ext/Cargo.toml`
+    Removing `{{ws}}/ext/Cargo.lock`
+    Removing directory `{{ext}}`
+    Updating {{ws}}/Cargo.lock
+"#;
+}
+
+fn cargo_new(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
+    Ok(())
+}
+
+fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
+    let modified = fs::read_to_string(manifest_path)?;
+    assert_diff!(expected, &modified, "\n", 0);
+    Ok(())
+}
+
+fn assert_stderr(stderr: &[u8], expected: &str) -> std::result::Result<(), Utf8Error> {
+    assert_diff!(expected, str::from_utf8(stderr)?, "\n", 0);
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<Metadata> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+}
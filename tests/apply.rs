@@ -0,0 +1,273 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_member::PlanStep;
+use cargo_metadata::{Metadata, MetadataCommand};
+use difference::assert_diff;
+use duct::cmd;
+use std::{env, fs, io, path::Path};
+use tempdir::TempDir;
+
+#[test]
+fn apply() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-apply")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&a, false)?;
+    cargo_new(&b, true)?;
+    fs::write(
+        a.join("Cargo.toml"),
+        fs::read_to_string(a.join("Cargo.toml"))? + "b = { path = \"../b\" }\n",
+    )?;
+    fs::create_dir(tempdir.path().join("crates"))?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let err = cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![
+            PlanStep::Cp {
+                src: "b".into(),
+                dst: "c".into(),
+                no_rename: false,
+            },
+            PlanStep::Rm {
+                paths: vec!["b".into()],
+                force: false,
+            },
+        ],
+    )
+    .dry_run(false)
+    .exec()
+    .unwrap_err();
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to remove",
+        err.to_string(),
+    );
+    assert!(!tempdir.path().join("c").exists());
+
+    let err = cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![
+            PlanStep::Rm {
+                paths: vec!["crates".into()],
+                force: true,
+            },
+            PlanStep::Mv {
+                src: "crates".into(),
+                dst: "d".into(),
+                no_rename: false,
+            },
+        ],
+    )
+    .dry_run(false)
+    .exec()
+    .unwrap_err();
+    assert_eq!(
+        format!(
+            "step 2 (`mv`): `{}` is not a package at that point",
+            tempdir.path().join("crates").display(),
+        ),
+        err.to_string(),
+    );
+    assert!(tempdir.path().join("crates").exists());
+
+    let err = cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![
+            PlanStep::Mv {
+                src: "b".into(),
+                dst: "e".into(),
+                no_rename: false,
+            },
+            PlanStep::Rm {
+                paths: vec!["e".into()],
+                force: false,
+            },
+        ],
+    )
+    .dry_run(false)
+    .exec()
+    .unwrap_err();
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to remove",
+        err.to_string(),
+    );
+    assert!(b.exists());
+
+    let err = cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![
+            PlanStep::Mv {
+                src: "a".into(),
+                dst: "c".into(),
+                no_rename: false,
+            },
+            PlanStep::Rm {
+                paths: vec!["b".into()],
+                force: false,
+            },
+        ],
+    )
+    .dry_run(false)
+    .exec()
+    .unwrap_err();
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to remove",
+        err.to_string(),
+    );
+    assert!(a.exists() && b.exists());
+
+    cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![
+            PlanStep::New {
+                path: "c".into(),
+                lib: true,
+                name: None,
+            },
+            PlanStep::Mv {
+                src: "b".into(),
+                dst: "crates".into(),
+                no_rename: false,
+            },
+            PlanStep::Cp {
+                src: "c".into(),
+                dst: "d".into(),
+                no_rename: false,
+            },
+            PlanStep::Exclude {
+                paths: vec!["d".into()],
+            },
+        ],
+    )
+    .dry_run(false)
+    .exec()?;
+
+    assert!(!b.exists());
+    assert!(tempdir.path().join("crates").join("b").exists());
+    assert!(tempdir.path().join("d").join("Cargo.toml").exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), APPLIED)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static APPLIED: &str = r#"[workspace]
+members = ["a", "c", "crates/b"]
+exclude = ["d"]
+"#;
+}
+
+#[test]
+fn replaced_dst() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-apply-replaced-dst")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&a, true)?;
+    fs::create_dir(&b)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![
+            PlanStep::Rm {
+                paths: vec!["b".into()],
+                force: true,
+            },
+            PlanStep::Cp {
+                src: "a".into(),
+                dst: "b".into(),
+                no_rename: false,
+            },
+            PlanStep::Rm {
+                paths: vec!["b".into()],
+                force: false,
+            },
+        ],
+    )
+    .dry_run(false)
+    .exec()?;
+
+    assert!(!b.exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
+#[test]
+fn step_context() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-apply-step-context")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&a, true)?;
+    cargo_new(&b, true)?;
+    fs::write(
+        b.join("Cargo.toml"),
+        fs::read_to_string(b.join("Cargo.toml"))? + "\n[workspace]\n",
+    )?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let err = cargo_member::Plan::from_metadata(
+        &metadata,
+        vec![PlanStep::Include {
+            paths: vec!["b".into()],
+            force: false,
+        }],
+    )
+    .dry_run(false)
+    .exec()
+    .unwrap_err();
+    let err = anyhow::Error::from(err);
+    assert_eq!(
+        vec![
+            "failed to apply step 1 (`include`)".to_owned(),
+            format!(
+                "`{}` is the root of another workspace. enable `--absorb` to merge it",
+                b.display(),
+            ),
+        ],
+        err.chain().map(ToString::to_string).collect::<Vec<_>>(),
+    );
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = ["b"]
+"#;
+}
+
+fn cargo_new(path: &Path, lib: bool) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    let kind = if lib { "--lib" } else { "--bin" };
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", kind, path).run()?;
+    Ok(())
+}
+
+fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
+    let modified = fs::read_to_string(manifest_path)?;
+    assert_diff!(expected, &modified, "\n", 0);
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<Metadata> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+}
@@ -51,12 +51,440 @@ exclude = []
 "#;
 }
 
+#[test]
+fn rebase_paths() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-cp-rebase-paths")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ROOT_MANIFEST)?;
+    fs::write(tempdir.path().join("README.md"), "")?;
+    cargo_new_lib(&tempdir.path().join("crates").join("a"))?;
+    cargo_new(&tempdir.path().join("crates").join("b"))?;
+    fs::write(
+        tempdir.path().join("crates").join("b").join("Cargo.toml"),
+        ORIGINAL,
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Cp::new(
+        &tempdir.path().join("crates").join("b"),
+        &tempdir.path().join("tools").join("sub").join("b2"),
+    )
+    .dry_run(false)
+    .stderr(NoColor::new(&mut stderr))
+    .exec()?;
+
+    assert_manifest(
+        &tempdir
+            .path()
+            .join("tools")
+            .join("sub")
+            .join("b2")
+            .join("Cargo.toml"),
+        EXPECTED_MANIFEST,
+    )?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replace(
+                "{{src}}",
+                &tempdir.path().join("crates").join("b").to_string_lossy(),
+            )
+            .replace(
+                "{{dst}}",
+                &tempdir
+                    .path()
+                    .join("tools")
+                    .join("sub")
+                    .join("b2")
+                    .to_string_lossy(),
+            )
+            .replace("{{root}}", &tempdir.path().to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    return Ok(());
+
+    static ROOT_MANIFEST: &str = r#"[workspace]
+members = ["crates/a", "crates/b"]
+"#;
+
+    static ORIGINAL: &str = r#"[package]
+name = "b"
+version = "0.1.0"
+edition = "2018"
+readme = "../../README.md"
+
+[dependencies]
+a = { path = "../a" }
+
+[[bin]]
+name = "b"
+path = "src/main.rs"
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[package]
+name = "b2"
+version = "0.1.0"
+edition = "2018"
+readme = "../../../README.md"
+
+[dependencies]
+a = { path = "../../../crates/a" }
+
+[[bin]]
+name = "b"
+path = "src/main.rs"
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{src}}` to `{{dst}}`
+    Rebasing `package.readme` from "../../README.md" to "../../../README.md"
+    Rebasing `a.path` from "../a" to "../../../crates/a"
+       Found workspace at {{root}}
+      Adding "tools/sub/b2" to `workspace.members`
+"#;
+}
+
+#[test]
+fn inherit() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-cp-inherit")?;
+
+    let x = tempdir.path().join("x");
+    fs::create_dir_all(x.join("crates"))?;
+    fs::write(x.join("Cargo.toml"), X_MANIFEST)?;
+    cargo_new_lib(&x.join("crates").join("a"))?;
+    cargo_new(&x.join("crates").join("b"))?;
+    fs::write(x.join("crates").join("b").join("Cargo.toml"), B_MANIFEST)?;
+    cargo_metadata(&x.join("Cargo.toml"), &[])?;
+
+    let y = tempdir.path().join("y");
+    fs::create_dir(&y)?;
+    fs::write(y.join("Cargo.toml"), Y_MANIFEST)?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Cp::new(&x.join("crates").join("b"), &y.join("b"))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&y.join("Cargo.toml"), EXPECTED_Y_MANIFEST)?;
+    assert_manifest(&y.join("b").join("Cargo.toml"), B_MANIFEST)?;
+    cargo_metadata(&y.join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Cp::new(&x.join("crates").join("b"), &tempdir.path().join("c"))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(
+        &tempdir.path().join("c").join("Cargo.toml"),
+        EXPECTED_C_MANIFEST,
+    )?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_C_STDERR
+            .replace("{{src}}", &x.join("crates").join("b").to_string_lossy())
+            .replace("{{dst}}", &tempdir.path().join("c").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("c").join("Cargo.toml"), &[])?;
+    return Ok(());
+
+    static X_MANIFEST: &str = r#"[workspace]
+members = ["crates/a", "crates/b"]
+
+[workspace.package]
+version = "0.2.0"
+edition = "2018"
+
+[workspace.dependencies]
+a = { path = "crates/a", default-features = false }
+"#;
+
+    static B_MANIFEST: &str = r#"[package]
+name = "b"
+version = { workspace = true }
+edition = { workspace = true }
+
+[dependencies]
+a = { workspace = true, optional = true }
+"#;
+
+    static Y_MANIFEST: &str = r#"[workspace]
+members = []
+
+[workspace.package]
+edition = "2018"
+"#;
+
+    static EXPECTED_Y_MANIFEST: &str = r#"[workspace]
+members = ["b"]
+exclude = []
+
+[workspace.package]
+edition = "2018"
+version = "0.2.0"
+
+[workspace.dependencies]
+a = { path = "../x/crates/a", default-features = false }
+"#;
+
+    static EXPECTED_C_MANIFEST: &str = r#"[package]
+name = "c"
+version = "0.2.0"
+edition = "2018"
+
+[dependencies]
+a = { optional = true, path = "../x/crates/a", default-features = false }
+"#;
+
+    static EXPECTED_C_STDERR: &str = r#"     Copying `{{src}}` to `{{dst}}`
+    Inlining `workspace.package.version`
+    Inlining `workspace.package.edition`
+    Inlining `workspace.dependencies.a`
+"#;
+}
+
+#[test]
+fn inherit_dotted_keys() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-cp-inherit-dotted-keys")?;
+
+    let x = tempdir.path().join("x");
+    fs::create_dir_all(x.join("crates"))?;
+    fs::write(x.join("Cargo.toml"), X_MANIFEST)?;
+    cargo_new_lib(&x.join("crates").join("a"))?;
+    cargo_new(&x.join("crates").join("b"))?;
+    fs::write(x.join("crates").join("b").join("Cargo.toml"), B_MANIFEST)?;
+    cargo_metadata(&x.join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Cp::new(&x.join("crates").join("b"), &tempdir.path().join("c"))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(
+        &tempdir.path().join("c").join("Cargo.toml"),
+        EXPECTED_C_MANIFEST,
+    )?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_C_STDERR
+            .replace("{{src}}", &x.join("crates").join("b").to_string_lossy())
+            .replace("{{dst}}", &tempdir.path().join("c").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("c").join("Cargo.toml"), &[])?;
+    return Ok(());
+
+    static X_MANIFEST: &str = r#"[workspace]
+members = ["crates/a", "crates/b"]
+
+[workspace.package]
+version = "0.2.0"
+edition = "2018"
+
+[workspace.dependencies]
+a = { path = "crates/a", default-features = false }
+"#;
+
+    static B_MANIFEST: &str = r#"[package]
+name = "b"
+version.workspace = true
+edition.workspace = true
+
+[dependencies]
+a.workspace = true
+a.optional = true
+"#;
+
+    static EXPECTED_C_MANIFEST: &str = r#"[package]
+name = "c"
+version = "0.2.0"
+edition = "2018"
+
+[dependencies]
+a.optional = true
+a.path = "../x/crates/a"
+a.default-features = false
+"#;
+
+    static EXPECTED_C_STDERR: &str = r#"     Copying `{{src}}` to `{{dst}}`
+    Inlining `workspace.package.version`
+    Inlining `workspace.package.edition`
+    Inlining `workspace.dependencies.a`
+"#;
+}
+
+#[test]
+fn rejected() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-cp-rejected")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let err = cargo_member::Cp::new(&tempdir.path().join("a"), &tempdir.path().join("b"))
+        .no_rename(true)
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    match err {
+        cargo_member::Error::Cargo { message, .. } => {
+            assert!(message.starts_with(&format!(
+                "Cargo rejected the modified `{}`",
+                tempdir.path().join("Cargo.toml").display(),
+            )));
+            assert!(message.contains("two packages named `a`"));
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    assert!(!tempdir.path().join("b").exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
+#[test]
+fn ignore() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-cp-ignore")?;
+    let src = tempdir.path().join("a");
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&src)?;
+    fs::write(src.join(".gitignore"), "/generated\n")?;
+    for file in &["generated/x.rs", "target/debug/a", ".git/HEAD", "notes.tmp"] {
+        let file = src.join(file);
+        fs::create_dir_all(file.parent().unwrap())?;
+        fs::write(file, "")?;
+    }
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let dst = tempdir.path().join("b");
+    cargo_member::Cp::new(&src, &dst)
+        .exclude(["*.tmp"])
+        .dry_run(false)
+        .exec()?;
+    assert!(dst.join(".gitignore").exists());
+    assert!(dst.join("src").join("main.rs").exists());
+    assert!(!dst.join("generated").exists());
+    assert!(!dst.join("target").exists());
+    assert!(!dst.join(".git").exists());
+    assert!(!dst.join("notes.tmp").exists());
+
+    let dst = tempdir.path().join("c");
+    cargo_member::Cp::new(&src, &dst)
+        .no_ignore(true)
+        .include_vcs(true)
+        .dry_run(false)
+        .exec()?;
+    assert!(dst.join("generated").join("x.rs").exists());
+    assert!(dst.join(".git").join("HEAD").exists());
+    assert!(dst.join("notes.tmp").exists());
+    assert!(!dst.join("target").exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+"#;
+}
+
+#[cfg(unix)]
+#[test]
+fn preserve() -> anyhow::Result<()> {
+    use std::{
+        os::unix::fs::{symlink, PermissionsExt as _},
+        time::{Duration, SystemTime},
+    };
+
+    let tempdir = TempDir::new("cargo-member-test-cp-preserve")?;
+    let (src, dst) = (
+        tempdir.path().join("crates").join("a"),
+        tempdir.path().join("tools").join("sub").join("a2"),
+    );
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    fs::write(tempdir.path().join("README.md"), "")?;
+    cargo_new(&src)?;
+    fs::write(src.join("run.sh"), "#!/bin/sh\n")?;
+    fs::set_permissions(src.join("run.sh"), fs::Permissions::from_mode(0o755))?;
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::options()
+        .write(true)
+        .open(src.join("run.sh"))?
+        .set_modified(mtime)?;
+    fs::create_dir_all(src.join("fixtures").join("empty"))?;
+    fs::set_permissions(src.join("fixtures"), fs::Permissions::from_mode(0o700))?;
+    fs::set_permissions(
+        src.join("fixtures").join("empty"),
+        fs::Permissions::from_mode(0o555),
+    )?;
+    symlink("src/main.rs", src.join("main.rs"))?;
+    symlink("../../README.md", src.join("README.md"))?;
+    symlink(src.join("src").join("main.rs"), src.join("main-abs.rs"))?;
+    symlink(tempdir.path().join("README.md"), src.join("README-abs.md"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Cp::new(&src, &dst).dry_run(false).exec()?;
+
+    let metadata = fs::metadata(dst.join("run.sh"))?;
+    assert_eq!(0o755, metadata.permissions().mode() & 0o777);
+    assert_eq!(mtime, metadata.modified()?);
+    assert!(dst.join("fixtures").join("empty").is_dir());
+    assert_eq!(
+        0o700,
+        fs::metadata(dst.join("fixtures"))?.permissions().mode() & 0o777,
+    );
+    assert_eq!(
+        0o555,
+        fs::metadata(dst.join("fixtures").join("empty"))?
+            .permissions()
+            .mode()
+            & 0o777,
+    );
+    assert_eq!(
+        Path::new("src/main.rs"),
+        fs::read_link(dst.join("main.rs"))?
+    );
+    assert_eq!(
+        Path::new("../../../README.md"),
+        fs::read_link(dst.join("README.md"))?,
+    );
+    assert!(dst.join("README.md").exists());
+    assert_eq!(
+        dst.join("src").join("main.rs"),
+        fs::read_link(dst.join("main-abs.rs"))?,
+    );
+    assert_eq!(
+        tempdir.path().join("README.md"),
+        fs::read_link(dst.join("README-abs.md"))?,
+    );
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["crates/a"]
+"#;
+}
+
 fn cargo_new(path: &Path) -> io::Result<()> {
     let cargo_exe = env::var("CARGO").unwrap();
     cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
     Ok(())
 }
 
+fn cargo_new_lib(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", "--lib", path).run()?;
+    Ok(())
+}
+
 fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
     let modified = fs::read_to_string(manifest_path)?;
     assert_diff!(expected, &modified, "\n", 0);
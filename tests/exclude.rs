@@ -23,7 +23,7 @@ fn normal() -> anyhow::Result<()> {
 
     let mut stderr = vec![];
 
-    cargo_member::Exclude::from_metadata(&metadata, &[tempdir.path().join("b")], &["c"])
+    cargo_member::Exclude::from_metadata(&metadata, &[tempdir.path().join("b")], ["c"])
         .dry_run(false)
         .stderr(NoColor::new(&mut stderr))
         .exec()?;
@@ -62,13 +62,16 @@ fn dry_run() -> anyhow::Result<()> {
 
     let mut stderr = vec![];
 
-    cargo_member::Exclude::from_metadata(&metadata, &[tempdir.path().join("b")], &["c"])
+    cargo_member::Exclude::from_metadata(&metadata, &[tempdir.path().join("b")], ["c"])
         .dry_run(true)
         .stderr(NoColor::new(&mut stderr))
         .exec()?;
 
     assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
-    assert_stderr(&stderr, EXPECTED_STDERR)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.toml").to_string_lossy()),
+    )?;
     cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
     return Ok(());
 
@@ -81,11 +84,138 @@ exclude = []
       Adding "b" to `workspace.exclude`
     Removing "c" from `workspace.members`
       Adding "c" to `workspace.exclude`
-warning: `workspace` unchanged
+--- {}
++++ {}
+@@ -1,3 +1,3 @@
+ [workspace]
+-members = ["a", "b", "c"]
+-exclude = []
++members = ["a"]
++exclude = ["b", "c"]
 warning: not modifying the manifest due to dry run
 "#;
 }
 
+#[test]
+fn default_members() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-exclude-default-members")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Exclude::from_metadata(&metadata, &[tempdir.path().join("b")], [""; 0])
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(&stderr, EXPECTED_STDERR)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+default-members = ["b"]
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = ["b"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"    Removing "b" from `workspace.members`
+      Adding "b" to `workspace.exclude`
+    Removing "b" from `workspace.default-members`
+    Removing `workspace.default-members` as it got empty
+"#;
+}
+
+#[test]
+fn glob() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-exclude-glob")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("crates").join("a"))?;
+    cargo_new(&tempdir.path().join("crates").join("b"))?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Exclude::from_metadata(
+        &metadata,
+        &[tempdir.path().join("crates").join("b")],
+        &[] as &[&str],
+    )
+    .glob_strategy(cargo_member::GlobStrategy::Exclude)
+    .dry_run(false)
+    .stderr(NoColor::new(&mut stderr))
+    .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(&stderr, EXPECTED_STDERR)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = ["crates/b"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"      Adding "crates/b" to `workspace.exclude`
+"#;
+}
+
+#[test]
+fn glob_expand() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-exclude-glob-expand")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("crates").join("a"))?;
+    cargo_new(&tempdir.path().join("crates").join("b"))?;
+    cargo_new(&tempdir.path().join("crates").join("c"))?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Exclude::from_metadata(
+        &metadata,
+        &[tempdir.path().join("crates").join("b")],
+        &[] as &[&str],
+    )
+    .glob_strategy(cargo_member::GlobStrategy::Expand)
+    .dry_run(false)
+    .stderr(NoColor::new(&mut stderr))
+    .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(&stderr, EXPECTED_STDERR)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["crates/a", "crates/c"]
+exclude = ["crates/b"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"   Expanding "crates/*" into ["crates/a", "crates/c"] in `workspace.members`
+      Adding "crates/b" to `workspace.exclude`
+"#;
+}
+
 fn cargo_new(path: &Path) -> io::Result<()> {
     let cargo_exe = env::var("CARGO").unwrap();
     cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
@@ -0,0 +1,187 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_metadata::{Metadata, MetadataCommand};
+use difference::assert_diff;
+use duct::cmd;
+use std::{
+    env, fs, io,
+    path::Path,
+    str::{self, Utf8Error},
+};
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+#[test]
+fn extract() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-extract")?;
+
+    let ws = tempdir.path().join("ws");
+    fs::create_dir(&ws)?;
+    fs::write(ws.join("Cargo.toml"), ORIGINAL)?;
+    cargo_new_lib(&ws.join("a"))?;
+    cargo_new(&ws.join("b"))?;
+    fs::write(ws.join("a").join("Cargo.toml"), A)?;
+    fs::write(ws.join("b").join("Cargo.toml"), B)?;
+    let metadata = cargo_metadata(&ws.join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Extract::from_metadata(&metadata, "b", tempdir.path())
+        .force(false)
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&ws.join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_manifest(&tempdir.path().join("b").join("Cargo.toml"), EXPECTED_B)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replace("{{src}}", &ws.join("b").to_string_lossy())
+            .replace("{{dst}}", &tempdir.path().join("b").to_string_lossy()),
+    )?;
+    assert!(!ws.join("b").exists());
+    cargo_metadata(&ws.join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+
+[workspace.package]
+version = "0.3.0"
+"#;
+
+    static A: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+"#;
+
+    static B: &str = r#"[package]
+name = "b"
+version = { workspace = true }
+edition = "2018"
+
+[dependencies]
+a = { path = "../a" }
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+
+[workspace.package]
+version = "0.3.0"
+"#;
+
+    static EXPECTED_B: &str = r#"[package]
+name = "b"
+version = "0.3.0"
+edition = "2018"
+
+[dependencies]
+a = "0.1.0"
+
+[workspace]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{src}}` to `{{dst}}`
+    Rebasing `a.path` from "../a" to "../ws/a"
+    Inlining `workspace.package.version`
+  Converting `a` to a version dependency ("0.1.0")
+      Adding `[workspace]` to `{{dst}}/Cargo.toml`
+    Removing directory `{{src}}`
+    Removing "b" from `workspace.members`
+"#;
+}
+
+#[test]
+fn dependents() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-extract-dependents")?;
+
+    let ws = tempdir.path().join("ws");
+    fs::create_dir(&ws)?;
+    fs::write(ws.join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&ws.join("a"))?;
+    cargo_new_lib(&ws.join("b"))?;
+    fs::write(ws.join("a").join("Cargo.toml"), A)?;
+    let metadata = cargo_metadata(&ws.join("Cargo.toml"), &[])?;
+
+    let err = cargo_member::Extract::from_metadata(&metadata, "b", tempdir.path())
+        .force(false)
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to extract",
+        err.to_string(),
+    );
+    assert!(ws.join("b").exists());
+    assert!(!tempdir.path().join("b").exists());
+    assert_manifest(&ws.join("Cargo.toml"), MANIFEST)?;
+
+    let err = cargo_member::Extract::new(&ws, &ws.join("b"), tempdir.path())
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to extract",
+        err.to_string(),
+    );
+    assert!(ws.join("b").exists());
+    assert!(!tempdir.path().join("b").exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static A: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+b = { path = "../b" }
+"#;
+}
+
+fn cargo_new(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
+    Ok(())
+}
+
+fn cargo_new_lib(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", "--lib", path).run()?;
+    Ok(())
+}
+
+fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
+    let modified = fs::read_to_string(manifest_path)?;
+    assert_diff!(expected, &modified, "\n", 0);
+    Ok(())
+}
+
+fn assert_stderr(stderr: &[u8], expected: &str) -> std::result::Result<(), Utf8Error> {
+    assert_diff!(expected, str::from_utf8(stderr)?, "\n", 0);
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<Metadata> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+}
@@ -51,6 +51,49 @@ exclude = []
 "#;
 }
 
+#[test]
+fn default_members() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-focus-default-members")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Focus::new(tempdir.path(), &tempdir.path().join("a"))
+        .default_members(true)
+        .dry_run(false)
+        .offline(true)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.lock").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+default-members = ["a", "b"]
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+default-members = ["a"]
+exclude = []
+"#;
+
+    static EXPECTED_STDERR: &str = r#"    Removing "b" from `workspace.default-members`
+    Updating {}
+"#;
+}
+
 fn cargo_new(path: &Path) -> io::Result<()> {
     let cargo_exe = env::var("CARGO").unwrap();
     cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
@@ -51,6 +51,367 @@ exclude = []
 "#;
 }
 
+#[test]
+fn glob() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-glob")?;
+
+    cargo_new(&tempdir.path().join("crates").join("a"))?;
+    cargo_new(&tempdir.path().join("crates").join("b"))?;
+    cargo_new(&tempdir.path().join("crates").join("a").join("c"))?;
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+
+    let mut stderr = vec![];
+
+    let paths = [
+        tempdir.path().join("crates").join("b"),
+        tempdir.path().join("crates").join("a").join("c"),
+    ];
+    cargo_member::Include::new(tempdir.path(), &paths)
+        .force(false)
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.lock").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = ["crates/b"]
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["crates/*", "crates/a/c"]
+exclude = []
+"#;
+
+    static EXPECTED_STDERR: &str = r#"    Removing "crates/b" from `workspace.exclude`
+      Adding "crates/a/c" to `workspace.members`
+    Updating {}
+"#;
+}
+
+#[test]
+fn default_member() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-default-member")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("b")])
+        .force(false)
+        .default_member(Some(true))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.lock").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a"]
+default-members = ["a"]
+exclude = ["b"]
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+default-members = ["a", "b"]
+exclude = []
+"#;
+
+    static EXPECTED_STDERR: &str = r#"      Adding "b" to `workspace.members`
+    Removing "b" from `workspace.exclude`
+      Adding "b" to `workspace.default-members`
+    Updating {}
+"#;
+}
+
+#[test]
+fn default_member_seeded() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-default-member-seeded")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("b")])
+        .force(false)
+        .default_member(Some(true))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.lock").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a"]
+exclude = ["b"]
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+default-members = ["a", "b"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"      Adding `workspace.default-members` with the current default members ["a"]
+      Adding "b" to `workspace.members`
+    Removing "b" from `workspace.exclude`
+      Adding "b" to `workspace.default-members`
+    Updating {}
+"#;
+}
+
+#[test]
+fn no_default() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-no-default")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("crates").join("a"))?;
+    cargo_new(&tempdir.path().join("crates").join("b"))?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("crates").join("b")])
+        .force(false)
+        .default_member(Some(false))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.lock").to_string_lossy()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = []
+default-members = ["crates/a"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"      Adding `workspace.default-members` with the current default members ["crates/*"]
+   Expanding "crates/*" into ["crates/a"] in `workspace.default-members`
+    Updating {}
+"#;
+}
+
+#[test]
+fn not_a_package() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-not-a-package")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    fs::create_dir(tempdir.path().join("a"))?;
+
+    let err = cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("a")])
+        .force(false)
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    match err {
+        cargo_member::Error::NotAPackage { path, action } => {
+            assert_eq!(tempdir.path().join("a"), path);
+            assert_eq!("add", action);
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = []
+exclude = []
+"#;
+}
+
+#[test]
+fn nested_workspace() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-nested-workspace")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    fs::write(tempdir.path().join("a").join("Cargo.toml"), A)?;
+
+    let err = cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("a")])
+        .force(false)
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    match err {
+        cargo_member::Error::NestedWorkspace { path } => {
+            assert_eq!(tempdir.path().join("a"), path);
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    assert_manifest(&tempdir.path().join("a").join("Cargo.toml"), A)?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = []
+exclude = ["a"]
+"#;
+
+    static A: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[workspace]
+"#;
+}
+
+#[test]
+fn absorb() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-absorb")?;
+    let a = tempdir.path().join("a");
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST_ORIGINAL)?;
+    cargo_new(&a)?;
+    cargo_new(&a.join("crates").join("b"))?;
+    fs::write(a.join("Cargo.toml"), A_ORIGINAL)?;
+    cargo_metadata(&a.join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Include::new(tempdir.path(), [&a])
+        .absorb(true)
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST_EXPECTED)?;
+    assert_manifest(&a.join("Cargo.toml"), A_EXPECTED)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{{root}}", &tempdir.path().to_string_lossy()),
+    )?;
+    assert!(!a.join("Cargo.lock").exists());
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static MANIFEST_ORIGINAL: &str = r#"[workspace]
+members = []
+exclude = ["a"]
+"#;
+
+    static A_ORIGINAL: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+b = { path = "crates/b" }
+
+[workspace]
+members = ["crates/*"]
+"#;
+
+    static MANIFEST_EXPECTED: &str = r#"[workspace]
+members = ["a", "a/crates/b"]
+exclude = []
+"#;
+
+    static A_EXPECTED: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+b = { path = "crates/b" }
+"#;
+
+    static EXPECTED_STDERR: &str = r#"   Absorbing `[workspace]` in `{{root}}/a/Cargo.toml`
+    Removing `{{root}}/a/Cargo.lock`
+      Adding "a" to `workspace.members`
+    Removing "a" from `workspace.exclude`
+      Adding "a/crates/b" to `workspace.members`
+    Updating {{root}}/Cargo.lock
+"#;
+}
+
+#[test]
+fn backup() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-backup")?;
+    let manifest_path = tempdir.path().join("Cargo.toml");
+
+    fs::write(&manifest_path, ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    fs::write(&manifest_path, ORIGINAL)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        fs::set_permissions(&manifest_path, fs::Permissions::from_mode(0o640))?;
+    }
+
+    cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("a")])
+        .backup(true)
+        .dry_run(false)
+        .exec()?;
+
+    assert_manifest(&manifest_path, EXPECTED_MANIFEST)?;
+    assert_manifest(&tempdir.path().join("Cargo.toml.bak"), ORIGINAL)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        assert_eq!(
+            0o640,
+            fs::metadata(&manifest_path)?.permissions().mode() & 0o777
+        );
+    }
+    assert_eq!(
+        ["Cargo.lock", "Cargo.toml", "Cargo.toml.bak", "a"][..],
+        *fs::read_dir(tempdir.path())?
+            .map(|e| Ok(e?.file_name().to_string_lossy().into_owned()))
+            .collect::<io::Result<std::collections::BTreeSet<_>>>()?
+            .into_iter()
+            .collect::<Vec<_>>(),
+    );
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = []
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
 #[test]
 fn force_nonexisting() -> anyhow::Result<()> {
     let tempdir = TempDir::new("cargo-member-test-include-force-nonexisting")?;
@@ -84,6 +445,40 @@ exclude = []
 "#;
 }
 
+#[test]
+fn rollback() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-include-rollback")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    fs::write(tempdir.path().join("b").join("Cargo.toml"), "[package]\n")?;
+    let lock = fs::read_to_string(tempdir.path().join("Cargo.lock"))?;
+
+    let err = cargo_member::Include::new(tempdir.path(), &[tempdir.path().join("b")])
+        .offline(true)
+        .exec()
+        .unwrap_err();
+
+    // Rejected before Cargo is asked to update the lock file.
+    match err {
+        cargo_member::Error::Cargo { args, message } => {
+            assert_eq!(["metadata", "--no-deps"], *args);
+            assert!(message.starts_with("Cargo rejected the modified"));
+        }
+        err => panic!("{:?}", err),
+    }
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    assert_manifest(&tempdir.path().join("Cargo.lock"), &lock)?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = ["b"]
+"#;
+}
+
 #[test]
 fn dry_run() -> anyhow::Result<()> {
     let tempdir = TempDir::new("cargo-member-test-include-dry-run")?;
@@ -102,7 +497,10 @@ fn dry_run() -> anyhow::Result<()> {
         .exec()?;
 
     assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
-    assert_stderr(&stderr, EXPECTED_STDERR)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR.replace("{}", &tempdir.path().join("Cargo.toml").to_string_lossy()),
+    )?;
     cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
     return Ok(());
 
@@ -113,7 +511,14 @@ exclude = ["b"]
 
     static EXPECTED_STDERR: &str = r#"      Adding "b" to `workspace.members`
     Removing "b" from `workspace.exclude`
-warning: `workspace` unchanged
+--- {}
++++ {}
+@@ -1,3 +1,3 @@
+ [workspace]
+-members = ["a"]
+-exclude = ["b"]
++members = ["a", "b"]
++exclude = []
 warning: not modifying the manifest due to dry run
 "#;
 }
@@ -0,0 +1,125 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_member::{
+    cli::{Cargo, Context},
+    ListedPackage, PackageStatus,
+};
+use cargo_metadata::{Metadata, MetadataCommand};
+use duct::cmd;
+use std::{env, fs, io, path::Path};
+use structopt::StructOpt as _;
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+#[test]
+fn list() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-list")?;
+
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    cargo_new(&tempdir.path().join("c"))?;
+    cargo_new(&tempdir.path().join("d"))?;
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    let packages = cargo_member::List::from_metadata(&metadata)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_eq!(
+        vec![
+            listed("a", PackageStatus::Member, true),
+            listed("b", PackageStatus::Member, false),
+            listed("c", PackageStatus::Excluded, false),
+            listed("d", PackageStatus::Inactive, false),
+        ],
+        packages,
+    );
+    assert!(stderr.is_empty());
+    assert_eq!(packages, cargo_member::List::new(tempdir.path()).exec()?,);
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+default-members = ["a"]
+exclude = ["c"]
+"#;
+
+    fn listed(name: &str, status: PackageStatus, default_member: bool) -> ListedPackage {
+        ListedPackage {
+            path: name.to_owned(),
+            name: Some(name.to_owned()),
+            status,
+            default_member,
+        }
+    }
+}
+
+#[test]
+fn cli() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-list-cli")?;
+
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("bb"))?;
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let list = |format: &str| -> anyhow::Result<String> {
+        let Cargo::Member(opt) = Cargo::from_iter_safe(&[
+            "cargo".as_ref(),
+            "member".as_ref(),
+            "list".as_ref(),
+            "--format".as_ref(),
+            format.as_ref(),
+            "--manifest-path".as_ref(),
+            tempdir.path().join("Cargo.toml").as_os_str(),
+        ])?;
+        let mut stdout = vec![];
+        let ctx = Context::new(NoColor::new(io::sink()))?.stdout(&mut stdout);
+        cargo_member::cli::run(opt, ctx)?;
+        Ok(String::from_utf8(stdout)?)
+    };
+
+    assert_eq!(HUMAN, list("human")?);
+    assert_eq!(
+        format!("{}\n", JSON.replace(['\n', ' '], "")),
+        list("json")?,
+    );
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+
+    static HUMAN: &str = "PATH  NAME  STATUS    DEFAULT
+a     a     member    yes
+bb    bb    inactive  no
+";
+
+    static JSON: &str = r#"[
+        {"path": "a", "name": "a", "status": "member", "default_member": true},
+        {"path": "bb", "name": "bb", "status": "inactive", "default_member": false}
+    ]"#;
+}
+
+fn cargo_new(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<Metadata> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+}
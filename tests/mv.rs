@@ -61,12 +61,553 @@ exclude = []
 "#;
 }
 
+#[test]
+fn dependents() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-dependents")?;
+
+    let expected_stderr = EXPECTED_STDERR
+        .replace("{{ws}}", &tempdir.path().to_string_lossy())
+        .replace("{{sep}}", std::path::MAIN_SEPARATOR.to_string().as_ref());
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    fs::write(tempdir.path().join("a").join("Cargo.toml"), A_ORIGINAL)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Mv::from_metadata(&metadata, "b", &tempdir.path().join("crates").join("b"))
+        .dry_run(false)
+        .no_rename(true)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_manifest(&tempdir.path().join("a").join("Cargo.toml"), A_EXPECTED)?;
+    assert_stderr(&stderr, &expected_stderr)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a", "crates/b"]
+exclude = []
+"#;
+
+    static A_ORIGINAL: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+b = { path = "../b" } # comment
+
+[target.'cfg(unix)'.dev-dependencies.b]
+path = "../b"
+"#;
+
+    static A_EXPECTED: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+b = { path = "../crates/b" } # comment
+
+[target.'cfg(unix)'.dev-dependencies.b]
+path = "../crates/b"
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{ws}}{{sep}}b` to `{{ws}}{{sep}}crates{{sep}}b`
+       Found workspace at {{ws}}
+      Adding "crates{{sep}}b" to `workspace.members`
+   Rewriting path dependencies in `{{ws}}{{sep}}a{{sep}}Cargo.toml`
+    Removing directory `{{ws}}{{sep}}b`
+    Removing "b" from `workspace.members`
+"#;
+}
+
+#[test]
+fn rename_alias() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-rename-alias")?;
+
+    let expected_stderr = EXPECTED_STDERR
+        .replace("{{ws}}", &tempdir.path().to_string_lossy())
+        .replace("{{sep}}", std::path::MAIN_SEPARATOR.to_string().as_ref());
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new_lib(&tempdir.path().join("b"))?;
+    fs::write(tempdir.path().join("a").join("Cargo.toml"), A_ORIGINAL)?;
+    fs::write(tempdir.path().join("b").join("Cargo.toml"), B)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Mv::from_metadata(&metadata, "b", &tempdir.path().join("c"))
+        .dry_run(false)
+        .rename_strategy(cargo_member::RenameStrategy::Alias)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("a").join("Cargo.toml"), A_EXPECTED)?;
+    assert_stderr(&stderr, &expected_stderr)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static B: &str = r#"[package]
+name = "b"
+version = "0.1.0"
+edition = "2018"
+
+[features]
+std = []
+"#;
+
+    static A_ORIGINAL: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[features]
+default = ["b/std"]
+
+[dependencies]
+b = { path = "../b", optional = true }
+"#;
+
+    static A_EXPECTED: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[features]
+default = ["b/std"]
+
+[dependencies]
+b = { path = "../c", optional = true, package = "c" }
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{ws}}{{sep}}b` to `{{ws}}{{sep}}c`
+       Found workspace at {{ws}}
+      Adding "c" to `workspace.members`
+   Rewriting path dependencies in `{{ws}}{{sep}}a{{sep}}Cargo.toml`
+      Adding `package = "c"` to `b`
+    Removing directory `{{ws}}{{sep}}b`
+    Removing "b" from `workspace.members`
+"#;
+}
+
+#[test]
+fn rename_key() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-rename-key")?;
+
+    let expected_stderr = EXPECTED_STDERR
+        .replace("{{ws}}", &tempdir.path().to_string_lossy())
+        .replace("{{sep}}", std::path::MAIN_SEPARATOR.to_string().as_ref());
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new_lib(&tempdir.path().join("b"))?;
+    fs::write(tempdir.path().join("a").join("Cargo.toml"), A_ORIGINAL)?;
+    fs::write(tempdir.path().join("b").join("Cargo.toml"), B)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Mv::from_metadata(&metadata, "b", &tempdir.path().join("c"))
+        .dry_run(false)
+        .rename_strategy(cargo_member::RenameStrategy::Key)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("a").join("Cargo.toml"), A_EXPECTED)?;
+    assert_stderr(&stderr, &expected_stderr)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static B: &str = r#"[package]
+name = "b"
+version = "0.1.0"
+edition = "2018"
+
+[features]
+std = []
+"#;
+
+    static A_ORIGINAL: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[features]
+default = ["b/std"]
+extra = ["b"]
+
+[dependencies]
+b = { path = "../b", optional = true }
+"#;
+
+    static A_EXPECTED: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[features]
+default = ["c/std"]
+extra = ["c"]
+
+[dependencies]
+c = { path = "../c", optional = true }
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{ws}}{{sep}}b` to `{{ws}}{{sep}}c`
+       Found workspace at {{ws}}
+      Adding "c" to `workspace.members`
+   Rewriting path dependencies in `{{ws}}{{sep}}a{{sep}}Cargo.toml`
+    Renaming dependency "b" to "c"
+    Removing directory `{{ws}}{{sep}}b`
+    Removing "b" from `workspace.members`
+"#;
+}
+
+#[test]
+fn cross_workspace() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-cross-workspace")?;
+    let (x, y) = (tempdir.path().join("x"), tempdir.path().join("y"));
+
+    let expected_stderr = EXPECTED_STDERR
+        .replace("{{x}}", &x.to_string_lossy())
+        .replace("{{y}}", &y.to_string_lossy())
+        .replace("{{sep}}", std::path::MAIN_SEPARATOR.to_string().as_ref());
+
+    fs::create_dir_all(&x)?;
+    fs::create_dir_all(&y)?;
+    fs::write(x.join("Cargo.toml"), X_ORIGINAL)?;
+    fs::write(y.join("Cargo.toml"), Y_ORIGINAL)?;
+    cargo_new_lib(&x.join("a"))?;
+    cargo_new(&x.join("b"))?;
+    cargo_new(&y.join("c"))?;
+    fs::write(x.join("a").join("Cargo.toml"), A)?;
+    fs::write(x.join("b").join("Cargo.toml"), B_ORIGINAL)?;
+    fs::write(y.join("c").join("Cargo.toml"), C_ORIGINAL)?;
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(
+        &cargo_exe,
+        "update",
+        "-q",
+        "-p",
+        "glob",
+        "--precise",
+        "0.3.0"
+    )
+    .dir(&x)
+    .run()?;
+    let metadata = cargo_metadata(&x.join("Cargo.toml"), &["--locked"])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Mv::from_metadata(&metadata, "a", &y.join("a"))
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&x.join("Cargo.toml"), X_EXPECTED)?;
+    assert_manifest(&y.join("Cargo.toml"), Y_EXPECTED)?;
+    assert_manifest(&x.join("b").join("Cargo.toml"), B_EXPECTED)?;
+    assert_manifest(&y.join("c").join("Cargo.toml"), C_EXPECTED)?;
+    assert_stderr(&stderr, &expected_stderr)?;
+    let metadata = cargo_metadata(&y.join("Cargo.toml"), &["--locked"])?;
+    let glob = metadata.packages.iter().find(|p| p.name == "glob").unwrap();
+    assert_eq!("0.3.0", glob.version.to_string());
+    cargo_metadata(&x.join("Cargo.toml"), &[])?;
+    return Ok(());
+
+    static X_ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+"#;
+
+    static X_EXPECTED: &str = r#"[workspace]
+members = [ "b"]
+exclude = []
+"#;
+
+    static Y_ORIGINAL: &str = r#"[workspace]
+members = ["c"]
+"#;
+
+    static Y_EXPECTED: &str = r#"[workspace]
+members = ["c", "a"]
+exclude = []
+"#;
+
+    static A: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+glob = "0.3.0"
+"#;
+
+    static B_ORIGINAL: &str = r#"[package]
+name = "b"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+a = { path = "../a" }
+"#;
+
+    static B_EXPECTED: &str = r#"[package]
+name = "b"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+a = { path = "../../y/a" }
+"#;
+
+    static C_ORIGINAL: &str = r#"[package]
+name = "c"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+a = { path = "../../x/a" }
+"#;
+
+    static C_EXPECTED: &str = r#"[package]
+name = "c"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+a = { path = "../a" }
+"#;
+
+    static EXPECTED_STDERR: &str = r#"     Copying `{{x}}{{sep}}a` to `{{y}}{{sep}}a`
+       Found workspace at {{y}}
+      Adding "a" to `workspace.members`
+   Migrating 1 locked package(s) to `{{y}}{{sep}}Cargo.lock`
+   Rewriting path dependencies in `{{x}}{{sep}}b{{sep}}Cargo.toml`
+   Rewriting path dependencies in `{{y}}{{sep}}c{{sep}}Cargo.toml`
+    Removing directory `{{x}}{{sep}}a`
+    Removing "a" from `workspace.members`
+    Updating {{y}}{{sep}}Cargo.lock
+"#;
+}
+
+#[test]
+fn vcs() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-vcs")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    fs::write(tempdir.path().join(".gitignore"), "/target\n")?;
+    cargo_new(&a)?;
+    fs::write(a.join(".gitignore"), ".env\n")?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    git(tempdir.path(), &["init", "-q"])?;
+    git(tempdir.path(), &["add", "."])?;
+    git(tempdir.path(), &["commit", "-q", "-m", "Initial commit"])?;
+    fs::write(a.join(".env"), "")?;
+    fs::write(a.join("notes.txt"), "")?;
+
+    fs::write(a.join("src").join("main.rs"), "fn main() {}\n")?;
+    let err = cargo_member::Mv::new(tempdir.path(), &a, &b)
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+    match err {
+        cargo_member::Error::Dirty { paths, action } => {
+            assert_eq!([a.canonicalize()?.join("src").join("main.rs")], *paths);
+            assert_eq!("move", action);
+        }
+        err => panic!("{:?}", err),
+    }
+    assert!(a.exists() && !b.exists());
+    git(tempdir.path(), &["checkout", "-q", "--", "."])?;
+
+    cargo_member::Mv::new(tempdir.path(), &a, &b)
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!a.exists());
+    assert!(b.join(".env").exists());
+    assert!(b.join("notes.txt").exists());
+    assert_eq!(
+        ".gitignore\nCargo.lock\nCargo.toml\nb/.gitignore\nb/Cargo.toml\nb/src/main.rs\n",
+        git(tempdir.path(), &["ls-files"])?,
+    );
+    assert_eq!(
+        "R100\ta/src/main.rs\tb/src/main.rs\n",
+        git(
+            tempdir.path(),
+            &["diff", "--cached", "--name-status", "--", "a/src", "b/src"],
+        )?,
+    );
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
+#[test]
+fn vcs_skipped() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-vcs-skipped")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", &a).run()?;
+    fs::create_dir_all(a.join("docs"))?;
+    fs::write(a.join("docs").join("notes.md"), "")?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    git(tempdir.path(), &["init", "-q"])?;
+    git(tempdir.path(), &["add", "."])?;
+    git(tempdir.path(), &["commit", "-q", "-m", "Initial commit"])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Mv::new(tempdir.path(), &a, &b)
+        .exclude(vec!["/docs".to_owned()])
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert!(str::from_utf8(&stderr)?.contains(&format!(
+        "warning: 1 tracked file(s) in `{}` are not moved. they will be removed from the index \
+         and deleted: `docs/notes.md`\n",
+        a.display(),
+    )));
+    assert!(!a.exists());
+    assert!(!b.join("docs").exists());
+    assert_eq!(
+        "Cargo.lock\nCargo.toml\nb/Cargo.toml\nb/src/main.rs\n",
+        git(tempdir.path(), &["ls-files"])?,
+    );
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
+#[test]
+fn dirty_dependents() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-dirty-dependents")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new_lib(&a)?;
+    cargo_new(&b)?;
+    fs::write(b.join("Cargo.toml"), B_MANIFEST)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    git(tempdir.path(), &["init", "-q"])?;
+    git(tempdir.path(), &["add", "."])?;
+    git(tempdir.path(), &["commit", "-q", "-m", "Initial commit"])?;
+    fs::write(
+        b.join("Cargo.toml"),
+        format!("{}\n[features]\n", B_MANIFEST),
+    )?;
+
+    let err = cargo_member::Mv::new(tempdir.path(), &a, &tempdir.path().join("c"))
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+    match err {
+        cargo_member::Error::Dirty { paths, .. } => {
+            assert_eq!([b.canonicalize()?.join("Cargo.toml")], *paths);
+        }
+        err => panic!("{:?}", err),
+    }
+    assert!(a.exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static B_MANIFEST: &str = r#"[package]
+name = "b"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+a = { path = "../a" }
+"#;
+}
+
+#[test]
+fn ignored() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-mv-ignored")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&a)?;
+    fs::write(a.join(".gitignore"), ".env\n")?;
+    fs::write(a.join(".env"), "")?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Mv::new(tempdir.path(), &a, &b)
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!a.exists());
+    assert!(b.join(".gitignore").exists());
+    assert!(b.join(".env").exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
 fn cargo_new(path: &Path) -> io::Result<()> {
     let cargo_exe = env::var("CARGO").unwrap();
     cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
     Ok(())
 }
 
+fn cargo_new_lib(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", "--lib", path).run()?;
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    cmd("git", args)
+        .dir(dir)
+        .env("GIT_AUTHOR_NAME", "a")
+        .env("GIT_AUTHOR_EMAIL", "a@example.com")
+        .env("GIT_COMMITTER_NAME", "a")
+        .env("GIT_COMMITTER_EMAIL", "a@example.com")
+        .stdout_capture()
+        .read()
+        .map(|stdout| stdout + "\n")
+}
+
 fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
     let modified = fs::read_to_string(manifest_path)?;
     assert_diff!(expected, &modified, "\n", 0);
@@ -0,0 +1,214 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_metadata::{Metadata, MetadataCommand};
+use difference::assert_diff;
+use duct::cmd;
+use std::{
+    env, fs, io,
+    path::Path,
+    str::{self, Utf8Error},
+};
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+#[test]
+fn restore() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-restore")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+    let trash = tempdir
+        .path()
+        .join("target")
+        .join("cargo-member")
+        .join("trash");
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&a)?;
+    cargo_new(&b)?;
+    fs::write(b.join("notes.txt"), "")?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Rm::new(tempdir.path(), [&b])
+        .trash(true)
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!b.exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), REMOVED)?;
+    let entries = fs::read_dir(&trash)?.collect::<io::Result<Vec<_>>>()?;
+    assert_eq!(1, entries.len());
+    assert!(entries[0].path().join("b").join("notes.txt").exists());
+
+    let mut stderr = vec![];
+
+    cargo_member::Restore::new(tempdir.path())
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert!(b.join("notes.txt").exists());
+    assert!(!entries[0].path().exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), RESTORED)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replace("{{b}}", &b.to_string_lossy())
+            .replace("{{root}}", &tempdir.path().to_string_lossy())
+            .replace("{{sep}}", std::path::MAIN_SEPARATOR.to_string().as_ref()),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+
+    let err = cargo_member::Restore::new(tempdir.path())
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+    assert_eq!(
+        format!("nothing to restore in `{}`", trash.display()),
+        err.to_string(),
+    );
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+default-members = ["b"]
+exclude = []
+"#;
+
+    static REMOVED: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+
+    static RESTORED: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+default-members = ["b"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"   Restoring `{{b}}`
+      Adding "b" to `workspace.members`
+      Adding "b" to `workspace.default-members`
+    Updating {{root}}{{sep}}Cargo.lock
+"#;
+}
+
+#[test]
+fn mv_trash() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-restore-mv-trash")?;
+    let crates = tempdir.path().join("crates");
+    let (a, b) = (crates.join("a"), crates.join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&a)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Mv::from_metadata(&metadata, "a", &b)
+        .no_rename(true)
+        .trash(true)
+        .dry_run(false)
+        .exec()?;
+
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    let packages = cargo_member::List::from_metadata(&metadata).exec()?;
+    assert_eq!(
+        vec!["crates/b"],
+        packages.iter().map(|p| &*p.path).collect::<Vec<_>>(),
+    );
+
+    let err = cargo_member::Restore::new(tempdir.path())
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+    assert_eq!(
+        format!(
+            "cannot restore `{}`: the workspace already has a member named `a`",
+            a.display(),
+        ),
+        err.to_string(),
+    );
+    assert!(!a.exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = []
+"#;
+}
+
+#[test]
+fn target_dir() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-restore-target-dir")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+    let target_dir = tempdir.path().join("out");
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    fs::create_dir_all(tempdir.path().join(".cargo"))?;
+    fs::write(
+        tempdir.path().join(".cargo").join("config.toml"),
+        "[build]\ntarget-dir = \"out\"\n",
+    )?;
+    cargo_new(&a)?;
+    cargo_new(&b)?;
+    fs::create_dir(&target_dir)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Rm::new(tempdir.path(), [&b])
+        .trash(true)
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!tempdir.path().join("target").exists());
+    let trash = target_dir.join("cargo-member").join("trash");
+    assert_eq!(1, fs::read_dir(&trash)?.count());
+    assert!(target_dir
+        .join("cargo-member")
+        .join("journal.jsonl")
+        .exists());
+
+    let packages = cargo_member::List::new(tempdir.path()).exec()?;
+    assert_eq!(
+        ["a"][..],
+        *packages.iter().map(|p| &*p.path).collect::<Vec<_>>(),
+    );
+
+    cargo_member::Restore::new(tempdir.path())
+        .dry_run(false)
+        .exec()?;
+    assert!(b.join("Cargo.toml").exists());
+    assert_eq!(0, fs::read_dir(&trash)?.count());
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+"#;
+}
+
+fn cargo_new(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
+    Ok(())
+}
+
+fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
+    let modified = fs::read_to_string(manifest_path)?;
+    assert_diff!(expected, &modified, "\n", 0);
+    Ok(())
+}
+
+fn assert_stderr(stderr: &[u8], expected: &str) -> std::result::Result<(), Utf8Error> {
+    assert_diff!(expected, str::from_utf8(stderr)?, "\n", 0);
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<Metadata> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+}
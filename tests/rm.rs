@@ -27,7 +27,7 @@ fn rm() -> anyhow::Result<()> {
 
     let mut stderr = vec![];
 
-    cargo_member::Rm::from_metadata(&metadata, &[tempdir.path().join("b")], &["c"])
+    cargo_member::Rm::from_metadata(&metadata, &[tempdir.path().join("b")], ["c"])
         .force(false)
         .dry_run(false)
         .stderr(NoColor::new(&mut stderr))
@@ -55,12 +55,188 @@ exclude = []
 "#;
 }
 
+#[test]
+fn glob() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-rm-glob")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&tempdir.path().join("crates").join("a"))?;
+    cargo_new(&tempdir.path().join("crates").join("b"))?;
+    cargo_new(&tempdir.path().join("c"))?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Rm::from_metadata(&metadata, &[] as &[&Path], ["b", "c"])
+        .force(false)
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replace(
+                "{{b}}",
+                &tempdir.path().join("crates").join("b").to_string_lossy(),
+            )
+            .replace("{{c}}", &tempdir.path().join("c").to_string_lossy()),
+    )?;
+    assert_eq!(
+        vec![tempdir.path().join("crates").join("a")],
+        fs::read_dir(tempdir.path().join("crates"))?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?,
+    );
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["crates/*", "c"]
+exclude = []
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["crates/*"]
+exclude = []
+"#;
+
+    static EXPECTED_STDERR: &str = r#"    Removing directory `{{b}}`
+    Removing directory `{{c}}`
+    Removing "c" from `workspace.members`
+"#;
+}
+
+#[test]
+fn dependents() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-rm-dependents")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new_lib(&tempdir.path().join("b"))?;
+    fs::write(tempdir.path().join("a").join("Cargo.toml"), A)?;
+    let metadata = cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    let err = cargo_member::Rm::from_metadata(&metadata, &[] as &[&Path], ["b"])
+        .force(false)
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to remove",
+        err.to_string(),
+    );
+    assert_eq!(
+        "`a` (normal)\n`a` (dev)",
+        anyhow::Error::from(err).root_cause().to_string(),
+    );
+    assert!(tempdir.path().join("b").exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), MANIFEST)?;
+
+    let err = cargo_member::Rm::new(tempdir.path(), [tempdir.path().join("b")])
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    assert_eq!(
+        "package `b` is required by other workspace members. enable `--force` to remove",
+        err.to_string(),
+    );
+    assert!(tempdir.path().join("b").exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static A: &str = r#"[package]
+name = "a"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+b = { path = "../b" }
+
+[dev-dependencies]
+b = { path = "../b" }
+"#;
+}
+
+#[test]
+fn dirty() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-rm-dirty")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    git(tempdir.path(), &["init", "-q"])?;
+    git(tempdir.path(), &["add", "."])?;
+    git(tempdir.path(), &["commit", "-q", "-m", "Initial commit"])?;
+    fs::write(tempdir.path().join("Cargo.toml"), MODIFIED)?;
+
+    let err = cargo_member::Rm::new(tempdir.path(), &[tempdir.path().join("b")])
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+
+    assert_eq!(
+        format!(
+            "1 file(s) have uncommitted changes. enable `--allow-dirty` to remove\n{}",
+            tempdir.path().canonicalize()?.join("Cargo.toml").display(),
+        ),
+        err.to_string(),
+    );
+    assert!(tempdir.path().join("b").exists());
+
+    cargo_member::Rm::new(tempdir.path(), &[tempdir.path().join("b")])
+        .allow_dirty(true)
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!tempdir.path().join("b").exists());
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static MODIFIED: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+
+[profile.release]
+lto = true
+"#;
+}
+
 fn cargo_new(path: &Path) -> io::Result<()> {
     let cargo_exe = env::var("CARGO").unwrap();
     cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
     Ok(())
 }
 
+fn cargo_new_lib(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", "--lib", path).run()?;
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> io::Result<()> {
+    cmd("git", args)
+        .dir(dir)
+        .env("GIT_AUTHOR_NAME", "a")
+        .env("GIT_AUTHOR_EMAIL", "a@example.com")
+        .env("GIT_COMMITTER_NAME", "a")
+        .env("GIT_COMMITTER_EMAIL", "a@example.com")
+        .run()?;
+    Ok(())
+}
+
 fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
     let modified = fs::read_to_string(manifest_path)?;
     assert_diff!(expected, &modified, "\n", 0);
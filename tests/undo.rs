@@ -0,0 +1,226 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_metadata::{Metadata, MetadataCommand};
+use difference::assert_diff;
+use duct::cmd;
+use std::{env, fs, io, path::Path, str};
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+#[test]
+fn undo() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-undo")?;
+    let (a, b, c) = (
+        tempdir.path().join("a"),
+        tempdir.path().join("b"),
+        tempdir.path().join("c"),
+    );
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    fs::create_dir(tempdir.path().join("target"))?;
+    cargo_new(&a)?;
+    cargo_new(&b)?;
+    fs::write(b.join("notes.txt"), "")?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    let lockfile = fs::read_to_string(tempdir.path().join("Cargo.lock"))?;
+
+    cargo_member::Cp::new(&a, &c).dry_run(false).exec()?;
+    cargo_member::Rm::new(tempdir.path(), [&b])
+        .trash(true)
+        .dry_run(false)
+        .exec()?;
+
+    let entries = cargo_member::Log::new(tempdir.path()).exec()?;
+    assert_eq!(
+        ["cp", "rm"],
+        *entries.iter().map(|e| &*e.operation).collect::<Vec<_>>(),
+    );
+    assert!(entries[0].created.contains(&c));
+    assert_eq!(b, entries[1].moved[0].from);
+
+    cargo_member::Undo::new(tempdir.path())
+        .dry_run(false)
+        .exec()?;
+
+    assert!(b.join("notes.txt").exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), COPIED)?;
+    assert_eq!(1, cargo_member::Log::new(tempdir.path()).exec()?.len());
+
+    cargo_member::Undo::new(tempdir.path())
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!c.exists());
+    assert_manifest(&tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    assert_eq!(
+        lockfile,
+        fs::read_to_string(tempdir.path().join("Cargo.lock"))?,
+    );
+    assert!(cargo_member::Log::new(tempdir.path()).exec()?.is_empty());
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+
+    cargo_member::Rm::new(tempdir.path(), [&b])
+        .dry_run(false)
+        .exec()?;
+
+    let err = cargo_member::Undo::new(tempdir.path())
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+    assert_eq!(
+        format!(
+            "`{}` was deleted by `rm` and cannot be brought back. enable `--trash` to make \
+             removals undoable",
+            b.display(),
+        ),
+        err.to_string(),
+    );
+
+    cargo_member::New::new(tempdir.path(), &c)
+        .dry_run(false)
+        .exec()?;
+
+    let entries = cargo_member::Log::new(tempdir.path()).exec()?;
+    let entry = entries.last().unwrap();
+    assert_eq!("new", entry.operation);
+    assert_eq!([tempdir.path().join("Cargo.lock")][..], *entry.lockfiles);
+    assert!(entry
+        .modified
+        .iter()
+        .all(|f| f.path.ends_with("Cargo.toml")));
+
+    cargo_member::Undo::new(tempdir.path())
+        .dry_run(false)
+        .exec()?;
+
+    assert!(!fs::read_to_string(tempdir.path().join("Cargo.lock"))?.contains(r#"name = "c""#));
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static COPIED: &str = r#"[workspace]
+members = ["a", "b", "c"]
+exclude = []
+"#;
+}
+
+#[test]
+fn git_index() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-undo-git-index")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    fs::create_dir(tempdir.path().join("target"))?;
+    fs::write(tempdir.path().join(".gitignore"), "/target/\n")?;
+    cargo_new(&a)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    git(tempdir.path(), &["init", "-q"])?;
+    git(tempdir.path(), &["add", "."])?;
+    git(tempdir.path(), &["commit", "-q", "-m", "Initial commit"])?;
+
+    cargo_member::Mv::new(tempdir.path(), &a, &b)
+        .trash(true)
+        .dry_run(false)
+        .exec()?;
+
+    let err = cargo_member::Undo::new(tempdir.path())
+        .dry_run(false)
+        .exec()
+        .unwrap_err();
+    assert_eq!(
+        format!(
+            "`{}` was modified by `mv` and cannot be restored",
+            tempdir.path().join(".git").join("index").display(),
+        ),
+        err.to_string(),
+    );
+    assert!(b.exists());
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a"]
+exclude = []
+"#;
+}
+
+#[test]
+fn journal_error() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-undo-journal-error")?;
+    let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+
+    fs::write(tempdir.path().join("Cargo.toml"), MANIFEST)?;
+    cargo_new(&a)?;
+    cargo_new(&b)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+    let journal = tempdir
+        .path()
+        .join("target")
+        .join("cargo-member")
+        .join("journal.jsonl");
+    fs::create_dir_all(&journal)?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Exclude::new(tempdir.path(), [&b])
+        .dry_run(false)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXCLUDED)?;
+    assert!(journal.is_dir());
+    let stderr = str::from_utf8(&stderr)?;
+    assert!(stderr.contains("warning: "), "{}", stderr);
+    assert!(stderr.contains(&*journal.to_string_lossy()), "{}", stderr);
+    return Ok(());
+
+    static MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static EXCLUDED: &str = r#"[workspace]
+members = ["a"]
+exclude = ["b"]
+"#;
+}
+
+fn cargo_new(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
+    Ok(())
+}
+
+fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
+    let modified = fs::read_to_string(manifest_path)?;
+    assert_diff!(expected, &modified, "\n", 0);
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<Metadata> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+}
+
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    cmd("git", args)
+        .dir(dir)
+        .env("GIT_AUTHOR_NAME", "a")
+        .env("GIT_AUTHOR_EMAIL", "a@example.com")
+        .env("GIT_COMMITTER_NAME", "a")
+        .env("GIT_COMMITTER_EMAIL", "a@example.com")
+        .stdout_capture()
+        .read()
+        .map(|stdout| stdout + "\n")
+}
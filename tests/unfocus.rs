@@ -0,0 +1,149 @@
+#![warn(rust_2018_idioms)]
+
+use cargo_metadata::MetadataCommand;
+use difference::assert_diff;
+use duct::cmd;
+use std::{
+    env, fs, io,
+    path::Path,
+    str::{self, Utf8Error},
+};
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+#[test]
+fn unfocus() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-unfocus")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    cargo_new(&tempdir.path().join("c"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Focus::new(tempdir.path(), &tempdir.path().join("a"))
+        .exclude(true)
+        .offline(true)
+        .exec()?;
+    cargo_member::Focus::new(tempdir.path(), &tempdir.path().join("a"))
+        .offline(true)
+        .exec()?;
+
+    let mut stderr = vec![];
+
+    cargo_member::Unfocus::new(tempdir.path())
+        .offline(true)
+        .stderr(NoColor::new(&mut stderr))
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    assert_stderr(
+        &stderr,
+        &EXPECTED_STDERR
+            .replacen(
+                "{}",
+                &tempdir.path().join("Cargo.toml").to_string_lossy(),
+                1,
+            )
+            .replacen(
+                "{}",
+                &tempdir.path().join("Cargo.lock").to_string_lossy(),
+                1,
+            ),
+    )?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    cargo_member::Unfocus::new(tempdir.path())
+        .exec()
+        .unwrap_err();
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = ["c"]
+"#;
+
+    static EXPECTED_STDERR: &str = r#"   Restoring `workspace` in `{}`
+    Updating {}
+"#;
+}
+
+#[test]
+fn modified() -> anyhow::Result<()> {
+    let tempdir = TempDir::new("cargo-member-test-unfocus-modified")?;
+
+    fs::write(tempdir.path().join("Cargo.toml"), ORIGINAL)?;
+    cargo_new(&tempdir.path().join("a"))?;
+    cargo_new(&tempdir.path().join("b"))?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &[])?;
+
+    cargo_member::Focus::new(tempdir.path(), &tempdir.path().join("a"))
+        .offline(true)
+        .exec()?;
+
+    let manifest = fs::read_to_string(tempdir.path().join("Cargo.toml"))?;
+    fs::write(tempdir.path().join("Cargo.toml"), manifest + MODIFICATION)?;
+
+    let err = cargo_member::Unfocus::new(tempdir.path())
+        .offline(true)
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("enable `--force`"));
+
+    cargo_member::Unfocus::new(tempdir.path())
+        .force(true)
+        .offline(true)
+        .exec()?;
+
+    assert_manifest(&tempdir.path().join("Cargo.toml"), EXPECTED_MANIFEST)?;
+    cargo_metadata(&tempdir.path().join("Cargo.toml"), &["--locked"])?;
+    return Ok(());
+
+    static ORIGINAL: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+"#;
+
+    static MODIFICATION: &str = r#"
+[profile.dev]
+opt-level = 1
+"#;
+
+    static EXPECTED_MANIFEST: &str = r#"[workspace]
+members = ["a", "b"]
+exclude = []
+
+[profile.dev]
+opt-level = 1
+"#;
+}
+
+fn cargo_new(path: &Path) -> io::Result<()> {
+    let cargo_exe = env::var("CARGO").unwrap();
+    cmd!(cargo_exe, "new", "-q", "--vcs", "none", path).run()?;
+    Ok(())
+}
+
+fn assert_manifest(manifest_path: &Path, expected: &str) -> io::Result<()> {
+    let modified = fs::read_to_string(manifest_path)?;
+    assert_diff!(expected, &modified, "\n", 0);
+    Ok(())
+}
+
+fn assert_stderr(stderr: &[u8], expected: &str) -> std::result::Result<(), Utf8Error> {
+    assert_diff!(expected, str::from_utf8(stderr)?, "\n", 0);
+    Ok(())
+}
+
+fn cargo_metadata(manifest_path: &Path, opts: &[&str]) -> cargo_metadata::Result<()> {
+    let opts = opts
+        .iter()
+        .copied()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .other_options(opts.iter().map(ToOwned::to_owned).collect::<Vec<_>>())
+        .exec()
+        .map(drop)
+}